@@ -0,0 +1,104 @@
+use crate::{Point, Ray};
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    minimum: Point,
+    maximum: Point,
+}
+
+impl Aabb {
+    /// Builds the box spanned by two opposite corners, in any order. Degenerate dimensions are
+    /// padded slightly so that flat objects still have a volume to intersect.
+    pub fn new(a: Point, b: Point) -> Aabb {
+        const DELTA: f64 = 0.0001;
+        let mut minimum = Point::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let mut maximum = Point::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+        for axis in 0..3 {
+            if maximum[axis] - minimum[axis] < DELTA {
+                minimum[axis] -= DELTA / 2.0;
+                maximum[axis] += DELTA / 2.0;
+            }
+        }
+        Aabb { minimum, maximum }
+    }
+
+    /// Builds the smallest box containing all of `points`.
+    pub fn from_points(points: &[Point]) -> Aabb {
+        let mut minimum = points[0];
+        let mut maximum = points[0];
+        for p in points.iter().skip(1) {
+            for axis in 0..3 {
+                minimum[axis] = minimum[axis].min(p[axis]);
+                maximum[axis] = maximum[axis].max(p[axis]);
+            }
+        }
+        Aabb::new(minimum, maximum)
+    }
+
+    pub fn min(&self) -> Point {
+        self.minimum
+    }
+
+    pub fn max(&self) -> Point {
+        self.maximum
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    /// All eight corners of the box.
+    pub fn corners(&self) -> [Point; 8] {
+        let (a, b) = (self.minimum, self.maximum);
+        [
+            Point::new(a.x(), a.y(), a.z()),
+            Point::new(b.x(), a.y(), a.z()),
+            Point::new(a.x(), b.y(), a.z()),
+            Point::new(b.x(), b.y(), a.z()),
+            Point::new(a.x(), a.y(), b.z()),
+            Point::new(b.x(), a.y(), b.z()),
+            Point::new(a.x(), b.y(), b.z()),
+            Point::new(b.x(), b.y(), b.z()),
+        ]
+    }
+
+    /// Returns the parametric interval over which the ray overlaps the box, if any.
+    pub fn hit_interval(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction()[a];
+            let mut t0 = (self.minimum[a] - r.origin()[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - r.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.hit_interval(r, t_min, t_max).is_some()
+    }
+
+    pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
+        let small = Point::new(
+            box0.minimum.x().min(box1.minimum.x()),
+            box0.minimum.y().min(box1.minimum.y()),
+            box0.minimum.z().min(box1.minimum.z()),
+        );
+        let big = Point::new(
+            box0.maximum.x().max(box1.maximum.x()),
+            box0.maximum.y().max(box1.maximum.y()),
+            box0.maximum.z().max(box1.maximum.z()),
+        );
+        Aabb {
+            minimum: small,
+            maximum: big,
+        }
+    }
+}
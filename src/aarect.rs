@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use crate::{Aabb, HitRecord, Hittable, MaterialPtr, Point, Ray, Vec3};
+
+// Intersects a rectangle lying in the plane `axes.2 = k`, spanning [a0, a1] along `axes.0` and
+// [b0, b1] along `axes.1`. The outward normal always points along the positive `axes.2` axis.
+#[allow(clippy::too_many_arguments)]
+fn hit_aa_rect(
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    axes: (u8, u8, u8),
+    (a0, a1): (f64, f64),
+    (b0, b1): (f64, f64),
+    k: f64,
+    mat_ptr: &MaterialPtr,
+) -> Option<HitRecord> {
+    let (a, b, c) = axes;
+    let t = (k - r.origin()[c]) / r.direction()[c];
+    if !t.is_finite() || t < t_min || t > t_max {
+        return None;
+    }
+
+    let p = r.at(t);
+    if p[a] < a0 || p[a] > a1 || p[b] < b0 || p[b] > b1 {
+        return None;
+    }
+
+    let mut outward_normal = Vec3::new(0.0, 0.0, 0.0);
+    outward_normal[c] = 1.0;
+    let (front_face, normal) = HitRecord::get_face_normal(r, outward_normal);
+    let mut dpdu = Vec3::new(0.0, 0.0, 0.0);
+    dpdu[a] = 1.0;
+
+    Some(HitRecord {
+        p,
+        normal,
+        mat_ptr: Arc::clone(mat_ptr),
+        t,
+        u: (p[a] - a0) / (a1 - a0),
+        v: (p[b] - b0) / (b1 - b0),
+        dpdu,
+        front_face,
+    })
+}
+
+pub struct XyRect {
+    x0: f64,
+    x1: f64,
+    y0: f64,
+    y1: f64,
+    k: f64,
+    mat_ptr: MaterialPtr,
+}
+
+impl XyRect {
+    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, m: MaterialPtr) -> XyRect {
+        XyRect {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for XyRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_aa_rect(
+            r,
+            t_min,
+            t_max,
+            (0, 1, 2),
+            (self.x0, self.x1),
+            (self.y0, self.y1),
+            self.k,
+            &self.mat_ptr,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Aabb::new pads the zero-width dimension
+        Some(Aabb::new(
+            Point::new(self.x0, self.y0, self.k),
+            Point::new(self.x1, self.y1, self.k),
+        ))
+    }
+}
+
+pub struct XzRect {
+    x0: f64,
+    x1: f64,
+    z0: f64,
+    z1: f64,
+    k: f64,
+    mat_ptr: MaterialPtr,
+}
+
+impl XzRect {
+    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, m: MaterialPtr) -> XzRect {
+        XzRect {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for XzRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_aa_rect(
+            r,
+            t_min,
+            t_max,
+            (0, 2, 1),
+            (self.x0, self.x1),
+            (self.z0, self.z1),
+            self.k,
+            &self.mat_ptr,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Aabb::new pads the zero-width dimension
+        Some(Aabb::new(
+            Point::new(self.x0, self.k, self.z0),
+            Point::new(self.x1, self.k, self.z1),
+        ))
+    }
+}
+
+pub struct YzRect {
+    y0: f64,
+    y1: f64,
+    z0: f64,
+    z1: f64,
+    k: f64,
+    mat_ptr: MaterialPtr,
+}
+
+impl YzRect {
+    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, m: MaterialPtr) -> YzRect {
+        YzRect {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for YzRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_aa_rect(
+            r,
+            t_min,
+            t_max,
+            (1, 2, 0),
+            (self.y0, self.y1),
+            (self.z0, self.z1),
+            self.k,
+            &self.mat_ptr,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Aabb::new pads the zero-width dimension
+        Some(Aabb::new(
+            Point::new(self.k, self.y0, self.z0),
+            Point::new(self.k, self.y1, self.z1),
+        ))
+    }
+}
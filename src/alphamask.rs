@@ -0,0 +1,82 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::{
+    Color, HitRecord, Material, MaterialPtr, Ray, SampledSpectrum, SampledWavelengths, TexturePtr,
+};
+
+enum AlphaMode {
+    Threshold(f64),
+    Stochastic,
+}
+
+/// Cuts holes in a material with the first channel of an opacity texture, for cards such as
+/// leaves and fences. Rays pass straight through the surface wherever it is cut out.
+pub struct AlphaMask {
+    base: MaterialPtr,
+    opacity: TexturePtr,
+    mode: AlphaMode,
+}
+
+impl AlphaMask {
+    /// Cuts out the surface wherever the opacity is below one half.
+    pub fn new(base: MaterialPtr, opacity: TexturePtr) -> AlphaMask {
+        AlphaMask {
+            base,
+            opacity,
+            mode: AlphaMode::Threshold(0.5),
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> AlphaMask {
+        self.mode = AlphaMode::Threshold(threshold);
+        self
+    }
+
+    /// Lets rays through with a probability of one minus the opacity, so partially opaque
+    /// regions blend smoothly instead of having hard edges.
+    pub fn stochastic(mut self) -> AlphaMask {
+        self.mode = AlphaMode::Stochastic;
+        self
+    }
+}
+
+// A number in [0,1) that is random across rays and hits, but always the same for a given ray
+// and hit. Different `salt`s give independent numbers for the same hit.
+pub(crate) fn hash_hit(r: &Ray, rec: &HitRecord, salt: u64) -> f64 {
+    let mut hasher = DefaultHasher::new();
+    salt.hash(&mut hasher);
+    for v in [r.origin(), r.direction(), &rec.p] {
+        for i in 0..3 {
+            v[i].to_bits().hash(&mut hasher);
+        }
+    }
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl Material for AlphaMask {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.base.scatter(ray_in, rec)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        self.base.scatter_spectral(ray_in, rec, lambdas)
+    }
+
+    fn passes_through(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        let opacity = self.opacity.value(rec.u, rec.v, &rec.p).x();
+        match self.mode {
+            AlphaMode::Threshold(threshold) => opacity < threshold,
+            AlphaMode::Stochastic => hash_hit(ray_in, rec, 0) >= opacity,
+        }
+    }
+
+    fn walks_inside(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.base.walks_inside(ray_in, rec)
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{Color, Image, Material, MaterialPtr, Point, Vec3, INFINITY};
+
+/// Arbitrary output variables: images rendered alongside the beauty pass for denoising and
+/// compositing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Aov {
+    /// Distance along the camera ray to the first hit, infinite where nothing was hit.
+    Depth,
+    /// World space shading normal at the first hit.
+    Normal,
+    /// Color of the first surface hit, as it tints the light it scatters.
+    Albedo,
+    /// Index of the top-level object hit, counting from 1, with 0 for nothing.
+    ObjectId,
+    /// Identifier of the material hit, counting from 1, with 0 for nothing.
+    MaterialId,
+    /// Texture coordinates of the first hit in red and green.
+    Uv,
+    /// World space position of the first hit.
+    Position,
+    /// Light emitted by the first surface hit or reaching the camera after one bounce.
+    Direct,
+    /// Light reaching the camera after two or more bounces.
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 9] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Uv,
+        Aov::Position,
+        Aov::Direct,
+        Aov::Indirect,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Uv => "uv",
+            Aov::Position => "position",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+}
+
+/// What one camera ray saw, for every output variable.
+#[derive(Debug, Copy, Clone)]
+pub struct AovSample {
+    pub depth: f64,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub object_id: Option<usize>,
+    pub material_id: Option<usize>,
+    pub uv: (f64, f64),
+    pub position: Point,
+    pub direct: Color,
+    pub indirect: Color,
+}
+
+impl Default for AovSample {
+    /// A ray that hit nothing.
+    fn default() -> Self {
+        AovSample {
+            depth: INFINITY,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            albedo: Color::new(0.0, 0.0, 0.0),
+            object_id: None,
+            material_id: None,
+            uv: (0.0, 0.0),
+            position: Point::new(0.0, 0.0, 0.0),
+            direct: Color::new(0.0, 0.0, 0.0),
+            indirect: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Numbers materials in the order they are added while building a scene, so that every hit on
+/// the same material gets the same identifier from one render to the next.
+#[derive(Default)]
+pub struct MaterialIds {
+    ids: HashMap<usize, usize>,
+}
+
+impl MaterialIds {
+    pub fn new() -> MaterialIds {
+        MaterialIds::default()
+    }
+
+    // Materials are told apart by where they live, which a pointer to a concrete material shares
+    // with the same pointer cast to a `MaterialPtr`
+    fn key<M: Material + ?Sized>(material: &Arc<M>) -> usize {
+        Arc::as_ptr(material) as *const () as usize
+    }
+
+    /// Numbers `material` unless it was added before.
+    pub fn add<M: Material + ?Sized>(&mut self, material: &Arc<M>) {
+        let next = self.ids.len();
+        self.ids.entry(MaterialIds::key(material)).or_insert(next);
+    }
+
+    /// `None` for materials that were never added.
+    pub fn id(&self, material: &MaterialPtr) -> Option<usize> {
+        self.ids.get(&MaterialIds::key(material)).copied()
+    }
+}
+
+// Running totals for one pixel
+#[derive(Clone)]
+struct AovPixel {
+    samples: u64,
+    depth: f64,
+    object_id: Option<usize>,
+    material_id: Option<usize>,
+    // Normal, albedo, uv, position, direct and indirect
+    sums: [Vec3; 6],
+}
+
+/// Accumulates output variables per pixel. Depth keeps the nearest sample and identifiers the
+/// first sample to hit something, since blending either across an edge would give values that
+/// belong to nothing. The rest are averaged.
+pub struct AovFilm {
+    width: usize,
+    height: usize,
+    pixels: Vec<AovPixel>,
+}
+
+impl AovFilm {
+    pub fn new(width: usize, height: usize) -> AovFilm {
+        let pixel = AovPixel {
+            samples: 0,
+            depth: INFINITY,
+            object_id: None,
+            material_id: None,
+            sums: [Vec3::new(0.0, 0.0, 0.0); 6],
+        };
+        AovFilm {
+            width,
+            height,
+            pixels: vec![pixel; width * height],
+        }
+    }
+
+    /// Adds a sample to pixel (i, j), counting rows up from the bottom.
+    pub fn add_sample(&mut self, i: usize, j: usize, sample: &AovSample) {
+        let pixel = &mut self.pixels[j * self.width + i];
+        pixel.samples += 1;
+        pixel.depth = pixel.depth.min(sample.depth);
+        pixel.object_id = pixel.object_id.or(sample.object_id);
+        pixel.material_id = pixel.material_id.or(sample.material_id);
+
+        let values = [
+            sample.normal,
+            sample.albedo,
+            Vec3::new(sample.uv.0, sample.uv.1, 0.0),
+            sample.position,
+            sample.direct,
+            sample.indirect,
+        ];
+        for (sum, value) in pixel.sums.iter_mut().zip(values) {
+            *sum += value;
+        }
+    }
+
+    /// The accumulated values of one output variable, with one channel for depth and
+    /// identifiers and three for the rest.
+    pub fn image(&self, aov: Aov) -> Image {
+        let id = |id: Option<usize>| id.map_or(0.0, |id| (id + 1) as f64);
+        let average = |pixel: &AovPixel, k: usize| {
+            let sum = pixel.sums[k];
+            let n = pixel.samples.max(1) as f64;
+            vec![sum.x() / n, sum.y() / n, sum.z() / n]
+        };
+
+        let mut data = Vec::with_capacity(self.width * self.height * 3);
+        for j in (0..self.height).rev() {
+            for i in 0..self.width {
+                let pixel = &self.pixels[j * self.width + i];
+                data.extend(match aov {
+                    Aov::Depth => vec![pixel.depth],
+                    Aov::ObjectId => vec![id(pixel.object_id)],
+                    Aov::MaterialId => vec![id(pixel.material_id)],
+                    Aov::Normal => average(pixel, 0),
+                    Aov::Albedo => average(pixel, 1),
+                    Aov::Uv => average(pixel, 2),
+                    Aov::Position => average(pixel, 3),
+                    Aov::Direct => average(pixel, 4),
+                    Aov::Indirect => average(pixel, 5),
+                });
+            }
+        }
+
+        let channels = match aov {
+            Aov::Depth | Aov::ObjectId | Aov::MaterialId => 1,
+            _ => 3,
+        };
+        Image::new(self.width, self.height, channels, data)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{degrees_to_radians, random_double, Degrees, Point, TexturePtr, Vec3, PI};
+
+/// The shape of a lens opening, which is also the shape that out of focus highlights (bokeh)
+/// take. Shapes lie within [-1,1]² and are scaled by the camera's lens radius.
+#[derive(Clone)]
+pub enum Aperture {
+    Circular,
+    /// A regular polygon formed by `blades` straight diaphragm blades.
+    Polygonal {
+        blades: u32,
+        rotation: Degrees,
+    },
+    /// An arbitrary shape from the first channel of a texture over [0,1]², where 1 is open and 0
+    /// blocked. Values in between let part of the light through.
+    Mask(TexturePtr),
+}
+
+impl Aperture {
+    // Attempts at sampling a mask before giving up on the ray
+    const MASK_TRIES: usize = 64;
+
+    /// A uniformly distributed point on the opening, in the xy plane. `None` means a mask
+    /// blocked every attempt.
+    pub fn sample(&self) -> Option<Vec3> {
+        match self {
+            Aperture::Circular => Some(Vec3::random_in_unit_disk()),
+            Aperture::Polygonal { blades, rotation } => {
+                // Pick one of the equal triangles fanning out from the center, then a point in it
+                let blades = (*blades).max(3);
+                let n = blades as f64;
+                let k = (random_double(0.0, n) as u32).min(blades - 1) as f64;
+                let start = degrees_to_radians(*rotation).0 + 2.0 * PI * k / n;
+                let a = Vec3::new(start.cos(), start.sin(), 0.0);
+                let b = Vec3::new(
+                    (start + 2.0 * PI / n).cos(),
+                    (start + 2.0 * PI / n).sin(),
+                    0.0,
+                );
+
+                let (mut s, mut t) = (random_double(0.0, 1.0), random_double(0.0, 1.0));
+                if s + t > 1.0 {
+                    (s, t) = (1.0 - s, 1.0 - t);
+                }
+                Some(s * a + t * b)
+            }
+            Aperture::Mask(mask) => (0..Aperture::MASK_TRIES).find_map(|_| {
+                let (u, v) = (random_double(0.0, 1.0), random_double(0.0, 1.0));
+                let p = Point::new(2.0 * u - 1.0, 2.0 * v - 1.0, 0.0);
+                (random_double(0.0, 1.0) < mask.value(u, v, &p).x()).then_some(p)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{Image, ImageTexture, SolidColor};
+
+    #[test]
+    fn polygonal_samples_stay_inside_the_blades() {
+        let blades = 6;
+        let aperture = Aperture::Polygonal {
+            blades,
+            rotation: Degrees(15.0),
+        };
+        // The inscribed circle of the polygon touches each side halfway between two corners
+        let apothem = (PI / blades as f64).cos();
+        let mut outside_inner_circle = 0;
+        for _ in 0..10000 {
+            let p = aperture.sample().unwrap();
+            assert_eq!(p.z(), 0.0);
+            for k in 0..blades {
+                let angle =
+                    degrees_to_radians(Degrees(15.0)).0 + (2 * k + 1) as f64 * PI / blades as f64;
+                let side_normal = Vec3::new(angle.cos(), angle.sin(), 0.0);
+                assert!(Vec3::dot(&p, &side_normal) <= apothem + 1e-12);
+            }
+            if p.length() > apothem {
+                outside_inner_circle += 1;
+            }
+        }
+        // The corners are reached too
+        assert!(outside_inner_circle > 0);
+    }
+
+    #[test]
+    fn masks_only_pass_open_regions() {
+        // Blocked on the left half and open on the right
+        let half = Aperture::Mask(Arc::new(ImageTexture::new(Image::new(
+            2,
+            1,
+            1,
+            vec![0.0, 1.0],
+        ))));
+        for _ in 0..1000 {
+            let p = half.sample().unwrap();
+            assert!(p.x() >= 0.0 && p.x() <= 1.0 && p.y().abs() <= 1.0);
+        }
+
+        let closed = Aperture::Mask(Arc::new(SolidColor::new(Vec3::new(0.0, 0.0, 0.0))));
+        assert!(closed.sample().is_none());
+    }
+}
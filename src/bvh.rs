@@ -0,0 +1,211 @@
+use std::cmp::Ordering;
+
+use crate::hittable::hit_opaque;
+use crate::{Aabb, HitRecord, Hittable, HittableList, HittableObj, Ray};
+
+/// A bounding volume hierarchy over a list of objects. Objects without a bounding box (such as
+/// infinite planes) are kept beside the tree and tested on every ray.
+pub struct BvhNode {
+    root: Option<BvhTree>,
+    unbounded: Vec<(usize, HittableObj)>,
+}
+
+// Objects keep their index in the list the hierarchy was built from
+enum BvhChild {
+    Object(usize, HittableObj),
+    Tree(Box<BvhTree>),
+}
+
+struct BvhTree {
+    left: BvhChild,
+    right: Option<BvhChild>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> BvhNode {
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        for (index, object) in list.into_objects().into_iter().enumerate() {
+            match object.bounding_box() {
+                Some(bbox) => bounded.push((bbox, index, object)),
+                None => unbounded.push((index, object)),
+            }
+        }
+
+        BvhNode {
+            root: (!bounded.is_empty()).then(|| BvhTree::build(bounded)),
+            unbounded,
+        }
+    }
+
+    /// Like `hit`, also returning the index of the object hit in the list the hierarchy was
+    /// built from.
+    pub fn hit_object(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(usize, HitRecord)> {
+        let mut temp_rec = self
+            .root
+            .as_ref()
+            .and_then(|root| root.hit_object(r, t_min, t_max));
+        let mut closest_so_far = temp_rec.as_ref().map_or(t_max, |(_, rec)| rec.t);
+
+        for (index, object) in self.unbounded.iter() {
+            if let Some(rec) = hit_opaque(object.as_ref(), r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                temp_rec = Some((*index, rec));
+            }
+        }
+
+        temp_rec
+    }
+}
+
+impl BvhChild {
+    fn hit_object(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(usize, HitRecord)> {
+        match self {
+            BvhChild::Object(index, object) => {
+                hit_opaque(object.as_ref(), r, t_min, t_max).map(|rec| (*index, rec))
+            }
+            BvhChild::Tree(tree) => tree.hit_object(r, t_min, t_max),
+        }
+    }
+}
+
+impl BvhTree {
+    // Builds the tree over a non-empty list of objects
+    fn build(mut objects: Vec<(Aabb, usize, HittableObj)>) -> BvhTree {
+        // Split along the axis in which the object centroids are most spread out
+        let centroids: Vec<_> = objects.iter().map(|(bbox, _, _)| bbox.centroid()).collect();
+        let extent = Aabb::from_points(&centroids);
+        let spread = extent.max() - extent.min();
+        let axis = if spread.x() > spread.y() && spread.x() > spread.z() {
+            0
+        } else if spread.y() > spread.z() {
+            1
+        } else {
+            2
+        };
+        objects.sort_by(|(a, _, _), (b, _, _)| {
+            a.centroid()[axis]
+                .partial_cmp(&b.centroid()[axis])
+                .unwrap_or(Ordering::Equal)
+        });
+
+        match objects.len() {
+            1 => {
+                let (bbox, index, object) = objects.pop().unwrap();
+                BvhTree {
+                    left: BvhChild::Object(index, object),
+                    right: None,
+                    bbox,
+                }
+            }
+            2 => {
+                let (box1, index1, right) = objects.pop().unwrap();
+                let (box0, index0, left) = objects.pop().unwrap();
+                BvhTree {
+                    left: BvhChild::Object(index0, left),
+                    right: Some(BvhChild::Object(index1, right)),
+                    bbox: Aabb::surrounding_box(&box0, &box1),
+                }
+            }
+            n => {
+                let upper = objects.split_off(n / 2);
+                let left = BvhTree::build(objects);
+                let right = BvhTree::build(upper);
+                BvhTree {
+                    bbox: Aabb::surrounding_box(&left.bbox, &right.bbox),
+                    left: BvhChild::Tree(Box::new(left)),
+                    right: Some(BvhChild::Tree(Box::new(right))),
+                }
+            }
+        }
+    }
+
+    fn hit_object(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(usize, HitRecord)> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+
+        let hit_left = self.left.hit_object(r, t_min, t_max);
+        let t_closest = hit_left.as_ref().map_or(t_max, |(_, rec)| rec.t);
+        let hit_right = self
+            .right
+            .as_ref()
+            .and_then(|right| right.hit_object(r, t_min, t_closest));
+
+        hit_right.or(hit_left)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.hit_object(r, t_min, t_max).map(|(_, rec)| rec)
+    }
+
+    /// `None` if any object is unbounded, or there are no objects at all.
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.root.as_ref().map(|root| root.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random_double, test_material, Plane, Point, Sphere, Vec3};
+
+    fn scene() -> HittableList {
+        let mut list = HittableList::new();
+        list.add(Box::new(Plane::new(
+            Point::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            test_material(),
+        )));
+        for i in 0..20 {
+            let center = Point::new(i as f64 - 10.0, (i % 3) as f64, (i % 5) as f64 - 2.0);
+            list.add(Box::new(Sphere::new(center, 0.4, test_material())));
+        }
+        list
+    }
+
+    #[test]
+    fn empty_list() {
+        let bvh = BvhNode::new(HittableList::new());
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(bvh.hit(&r, 0.001, f64::INFINITY).is_none());
+        assert!(bvh.bounding_box().is_none());
+    }
+
+    #[test]
+    fn matches_the_flat_list_with_unbounded_objects() {
+        let list = scene();
+        let bvh = BvhNode::new(scene());
+        assert!(bvh.bounding_box().is_none());
+
+        for _ in 0..1000 {
+            let origin = Point::new(0.0, 5.0, 10.0);
+            let target = Point::new(
+                random_double(-12.0, 12.0),
+                random_double(-2.0, 3.0),
+                random_double(-4.0, 4.0),
+            );
+            let r = Ray::new(origin, target - origin, 0.0);
+            let expected = list.hit_object(&r, 0.001, f64::INFINITY);
+            let found = bvh.hit_object(&r, 0.001, f64::INFINITY);
+            match (&expected, &found) {
+                (Some((i, a)), Some((j, b))) => {
+                    assert_eq!(i, j);
+                    assert!((a.p - b.p).length() < 1e-9);
+                }
+                (None, None) => (),
+                _ => panic!(
+                    "BVH and list disagree: {:?} {:?}",
+                    expected.map(|(i, rec)| (i, rec.p)),
+                    found.map(|(i, rec)| (i, rec.p))
+                ),
+            }
+        }
+    }
+}
@@ -1,6 +1,65 @@
-use crate::{Degrees, Point, Ray, Vec3};
+use std::sync::Arc;
 
-pub struct Camera {
+use crate::{
+    degrees_to_radians, random_double, Aperture, Degrees, Hittable, Point, Ray, Vec3, INFINITY, PI,
+};
+
+pub trait Camera {
+    /// The ray through the point (s, t) of the image, where (0, 0) is the lower left corner and
+    /// (1, 1) the upper right. `None` means nothing is seen there, such as outside the image
+    /// circle of a fisheye lens.
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray>;
+}
+
+pub type CameraPtr = Arc<dyn Camera + Send + Sync>;
+
+/// The position and orientation shared by every camera. `w` points backwards from the view
+/// direction, `u` to the right and `v` up.
+#[derive(Debug, Copy, Clone)]
+pub struct CameraBasis {
+    origin: Point,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl CameraBasis {
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vec3) -> CameraBasis {
+        let w = (lookfrom - lookat).unit_vector();
+        let u = Vec3::cross(&vup, &w).unit_vector();
+        let v = Vec3::cross(&w, &u);
+        CameraBasis {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+        }
+    }
+
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Converts a direction from camera space, with x right, y up and z forward.
+    pub fn local(&self, d: &Vec3) -> Vec3 {
+        d.x() * self.u + d.y() * self.v - d.z() * self.w
+    }
+}
+
+/// A perspective camera with a thin lens, which blurs whatever is away from the focus distance.
+pub struct PerspectiveCamera {
     origin: Point,
     lower_left_corner: Point,
     horizontal: Vec3,
@@ -8,10 +67,19 @@ pub struct Camera {
     u: Vec3,
     v: Vec3,
     w: Vec3,
+    focus_dist: f64,
+    // Normal of the plane in focus, which is w unless the lens is tilted
+    focus_normal: Vec3,
     lens_radius: f64,
+    aperture: Aperture,
+    cats_eye: f64,
+    aspect_ratio: f64,
+    time0: f64, // shutter open time
+    time1: f64, // shutter close time
 }
 
-impl Camera {
+impl PerspectiveCamera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Point,
         lookat: Point,
@@ -20,15 +88,16 @@ impl Camera {
         aspect_ratio: f64,
         aperture: f64,
         focus_dist: f64,
-    ) -> Camera {
-        let theta = crate::degrees_to_radians(vfov);
+        time0: f64,
+        time1: f64,
+    ) -> PerspectiveCamera {
+        let theta = degrees_to_radians(vfov);
         let h = (theta.0 / 2.0).tan();
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let w = (lookfrom - lookat).unit_vector();
-        let u = Vec3::cross(&vup, &w).unit_vector();
-        let v = Vec3::cross(&w, &u);
+        let basis = CameraBasis::new(lookfrom, lookat, vup);
+        let (u, v, w) = (basis.u(), basis.v(), basis.w());
 
         let origin = lookfrom;
         let horizontal = focus_dist * viewport_width * u;
@@ -37,7 +106,7 @@ impl Camera {
 
         let lens_radius = aperture / 2.0;
 
-        Camera {
+        PerspectiveCamera {
             origin,
             lower_left_corner,
             horizontal,
@@ -45,16 +114,523 @@ impl Camera {
             u,
             v,
             w,
+            focus_dist,
+            focus_normal: w,
             lens_radius,
+            aperture: Aperture::Circular,
+            cats_eye: 0.0,
+            aspect_ratio,
+            time0,
+            time1,
+        }
+    }
+
+    /// Sets the shape of the lens opening, which out of focus highlights take on.
+    pub fn with_aperture(mut self, aperture: Aperture) -> PerspectiveCamera {
+        self.aperture = aperture;
+        self
+    }
+
+    /// Sets the size of the lens opening as a photographer would, from the f-number and the
+    /// focal length in scene units, replacing the `aperture` given to `new`.
+    pub fn with_f_number(mut self, f_number: f64, focal_length: f64) -> PerspectiveCamera {
+        self.lens_radius = focal_length / (2.0 * f_number);
+        self
+    }
+
+    /// Clips the opening towards the edges of the image as the lens barrel would, squeezing
+    /// bokeh into cat's eye shapes and darkening the corners. At 1 the corners keep about 40% of
+    /// the opening.
+    pub fn with_cats_eye(mut self, strength: f64) -> PerspectiveCamera {
+        self.cats_eye = strength;
+        self
+    }
+
+    /// Moves the plane in focus to `focus_dist` along the view direction, keeping the field of
+    /// view.
+    pub fn with_focus_distance(mut self, focus_dist: f64) -> PerspectiveCamera {
+        let scale = focus_dist / self.focus_dist;
+        self.horizontal = scale * self.horizontal;
+        self.vertical = scale * self.vertical;
+        self.lower_left_corner = self.origin + scale * (self.lower_left_corner - self.origin);
+        self.focus_dist = focus_dist;
+        self
+    }
+
+    /// Focuses on whatever is seen through the point (s, t) of the image, leaving the focus
+    /// unchanged if nothing is there.
+    pub fn autofocus(self, world: &dyn Hittable, s: f64, t: f64) -> PerspectiveCamera {
+        let direction =
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        match world.hit(
+            &Ray::new(self.origin, direction, self.time0),
+            0.001,
+            INFINITY,
+        ) {
+            Some(rec) => {
+                let focus_dist = Vec3::dot(&(rec.p - self.origin), &-self.w);
+                self.with_focus_distance(focus_dist)
+            }
+            None => self,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+    /// Shifts the lens parallel to the image, by fractions of the image's width and height. This
+    /// reframes the shot without turning the camera, so vertical lines stay vertical.
+    pub fn with_shift(mut self, x: f64, y: f64) -> PerspectiveCamera {
+        self.lower_left_corner = self.lower_left_corner + x * self.horizontal + y * self.vertical;
+        self
+    }
+
+    /// Tilts the plane in focus by `tilt` about the axis through the center of the image at
+    /// `rotation` counterclockwise from horizontal. Tilting forwards about a horizontal axis lays
+    /// the plane down along the ground, keeping a whole landscape sharp, while tilting backwards
+    /// with a wide aperture gives the miniature look.
+    pub fn with_tilt(mut self, tilt: Degrees, rotation: Degrees) -> PerspectiveCamera {
+        let tilt = degrees_to_radians(tilt).0;
+        let rotation = degrees_to_radians(rotation).0;
+        let axis = rotation.cos() * self.u + rotation.sin() * self.v;
+        self.focus_normal = tilt.cos() * self.w + tilt.sin() * Vec3::cross(&axis, &self.w);
+        self
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let lens = self.aperture.sample()?;
+        if self.cats_eye > 0.0 {
+            // The barrel is a second opening the same size, displaced further from the first the
+            // further the pixel is from the center of the image
+            let half_diagonal = 0.5 * (self.aspect_ratio * self.aspect_ratio + 1.0).sqrt();
+            let x = (s - 0.5) * self.aspect_ratio / half_diagonal;
+            let y = (t - 0.5) / half_diagonal;
+            let barrel = self.cats_eye * Vec3::new(x, y, 0.0);
+            if (lens - barrel).length_squared() > 1.0 {
+                return None;
+            }
+        }
+
+        // Aim through the point where the pinhole ray meets the plane in focus
+        let direction =
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        let denominator = Vec3::dot(&direction, &self.focus_normal);
+        let target = if denominator < -1e-8 {
+            let distance = -self.focus_dist * Vec3::dot(&self.w, &self.focus_normal);
+            self.origin + (distance / denominator) * direction
+        } else {
+            self.origin + direction
+        };
+
+        let rd = self.lens_radius * lens;
         let offset = self.u * rd.x() + self.v * rd.y();
-        Ray::new(
+        Some(Ray::new(
             self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            target - self.origin - offset,
+            random_double(self.time0, self.time1),
+        ))
+    }
+}
+
+/// Parallel rays without perspective, for technical and isometric views. The view is
+/// `viewport_height` world units tall.
+pub struct OrthographicCamera {
+    basis: CameraBasis,
+    viewport_width: f64,
+    viewport_height: f64,
+    time0: f64,
+    time1: f64,
+}
+
+impl OrthographicCamera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lookfrom: Point,
+        lookat: Point,
+        vup: Vec3,
+        viewport_height: f64,
+        aspect_ratio: f64,
+        time0: f64,
+        time1: f64,
+    ) -> OrthographicCamera {
+        OrthographicCamera {
+            basis: CameraBasis::new(lookfrom, lookat, vup),
+            viewport_width: aspect_ratio * viewport_height,
+            viewport_height,
+            time0,
+            time1,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let offset = (s - 0.5) * self.viewport_width * self.basis.u()
+            + (t - 0.5) * self.viewport_height * self.basis.v();
+        Some(Ray::new(
+            self.basis.origin() + offset,
+            -self.basis.w(),
+            random_double(self.time0, self.time1),
+        ))
+    }
+}
+
+/// An equidistant fisheye, where the distance from the center of the image is proportional to
+/// the angle from the view direction. The image circle fills the height of the image and spans
+/// `fov`, which can be up to 360 degrees.
+pub struct FisheyeCamera {
+    basis: CameraBasis,
+    half_fov: f64,
+    aspect_ratio: f64,
+    time0: f64,
+    time1: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        lookfrom: Point,
+        lookat: Point,
+        vup: Vec3,
+        fov: Degrees,
+        aspect_ratio: f64,
+        time0: f64,
+        time1: f64,
+    ) -> FisheyeCamera {
+        FisheyeCamera {
+            basis: CameraBasis::new(lookfrom, lookat, vup),
+            half_fov: degrees_to_radians(fov).0.min(2.0 * PI) / 2.0,
+            aspect_ratio,
+            time0,
+            time1,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let x = 2.0 * (s - 0.5) * self.aspect_ratio;
+        let y = 2.0 * (t - 0.5);
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let theta = r * self.half_fov;
+        let phi = y.atan2(x);
+        let direction = Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        );
+        Some(Ray::new(
+            self.basis.origin(),
+            self.basis.local(&direction),
+            random_double(self.time0, self.time1),
+        ))
+    }
+}
+
+/// A full 360 by 180 degree panorama in the equirectangular (latitude-longitude) projection,
+/// centered on the view direction. The image should be twice as wide as it is tall.
+pub struct EquirectangularCamera {
+    basis: CameraBasis,
+    time0: f64,
+    time1: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(
+        lookfrom: Point,
+        lookat: Point,
+        vup: Vec3,
+        time0: f64,
+        time1: f64,
+    ) -> EquirectangularCamera {
+        EquirectangularCamera {
+            basis: CameraBasis::new(lookfrom, lookat, vup),
+            time0,
+            time1,
+        }
+    }
+}
+
+/// The camera space direction at a longitude and latitude in radians, with longitude zero
+/// looking forward and increasing to the right.
+pub(crate) fn spherical_direction(longitude: f64, latitude: f64) -> Vec3 {
+    Vec3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        latitude.cos() * longitude.cos(),
+    )
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let direction = spherical_direction(2.0 * PI * (s - 0.5), PI * (t - 0.5));
+        Some(Ray::new(
+            self.basis.origin(),
+            self.basis.local(&direction),
+            random_double(self.time0, self.time1),
+        ))
+    }
+}
+
+/// Renders the six 90 degree faces of a cube map into one image, laid out in a 3x2 grid: front,
+/// right and back on the top row, then left, up and down on the bottom row. Faces are square
+/// when the image is 3:2.
+pub struct CubeMapCamera {
+    basis: CameraBasis,
+    time0: f64,
+    time1: f64,
+}
+
+impl CubeMapCamera {
+    // Forward, right and up of each face in camera space, in layout order
+    const FACES: [[(f64, f64, f64); 3]; 6] = [
+        [(0.0, 0.0, 1.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)],
+        [(1.0, 0.0, 0.0), (0.0, 0.0, -1.0), (0.0, 1.0, 0.0)],
+        [(0.0, 0.0, -1.0), (-1.0, 0.0, 0.0), (0.0, 1.0, 0.0)],
+        [(-1.0, 0.0, 0.0), (0.0, 0.0, 1.0), (0.0, 1.0, 0.0)],
+        [(0.0, 1.0, 0.0), (1.0, 0.0, 0.0), (0.0, 0.0, -1.0)],
+        [(0.0, -1.0, 0.0), (1.0, 0.0, 0.0), (0.0, 0.0, 1.0)],
+    ];
+
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vec3, time0: f64, time1: f64) -> CubeMapCamera {
+        CubeMapCamera {
+            basis: CameraBasis::new(lookfrom, lookat, vup),
+            time0,
+            time1,
+        }
+    }
+}
+
+impl Camera for CubeMapCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let column = ((3.0 * s) as usize).min(2);
+        let row = if t >= 0.5 { 0 } else { 1 };
+        let a = 2.0 * (3.0 * s - column as f64) - 1.0;
+        let b = 2.0 * (2.0 * t - (1 - row) as f64) - 1.0;
+
+        let [forward, right, up] =
+            CubeMapCamera::FACES[3 * row + column].map(|(x, y, z)| Vec3::new(x, y, z));
+        let direction = forward + a * right + b * up;
+        Some(Ray::new(
+            self.basis.origin(),
+            self.basis.local(&direction),
+            random_double(self.time0, self.time1),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_material, Sphere};
+
+    fn assert_direction(r: &Ray, expected: Vec3) {
+        let found = r.direction().unit_vector();
+        assert!(
+            (found - expected.unit_vector()).length() < 1e-9,
+            "{found:?} {expected:?}"
+        );
+    }
+
+    // Looking down -x from (1, 2, 3), so camera right is -z and up is y
+    fn basis() -> (Point, Point, Vec3) {
+        (
+            Point::new(1.0, 2.0, 3.0),
+            Point::new(-4.0, 2.0, 3.0),
+            Vec3::new(0.0, 1.0, 0.0),
         )
     }
+
+    #[test]
+    fn perspective_rays() {
+        let (lookfrom, lookat, vup) = basis();
+        let cam = PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            Degrees(90.0),
+            2.0,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let r = cam.get_ray(0.5, 0.5).unwrap();
+        assert!((r.origin() - lookfrom).length() < 1e-12);
+        assert_direction(&r, Vec3::new(-1.0, 0.0, 0.0));
+        assert_direction(&cam.get_ray(0.5, 1.0).unwrap(), Vec3::new(-1.0, 1.0, 0.0));
+        assert_direction(&cam.get_ray(1.0, 0.5).unwrap(), Vec3::new(-1.0, 0.0, -2.0));
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let (lookfrom, lookat, vup) = basis();
+        let cam = OrthographicCamera::new(lookfrom, lookat, vup, 4.0, 1.5, 0.0, 1.0);
+        let r = cam.get_ray(0.0, 1.0).unwrap();
+        assert!((r.origin() - Point::new(1.0, 4.0, 6.0)).length() < 1e-12);
+        assert_direction(&r, Vec3::new(-1.0, 0.0, 0.0));
+        assert_direction(&cam.get_ray(0.9, 0.2).unwrap(), Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn fisheye_angles_grow_with_the_radius() {
+        let (lookfrom, lookat, vup) = basis();
+        let cam = FisheyeCamera::new(lookfrom, lookat, vup, Degrees(180.0), 2.0, 0.0, 1.0);
+        assert_direction(&cam.get_ray(0.5, 0.5).unwrap(), Vec3::new(-1.0, 0.0, 0.0));
+        assert_direction(&cam.get_ray(0.5, 1.0).unwrap(), Vec3::new(0.0, 1.0, 0.0));
+        assert_direction(&cam.get_ray(0.5, 0.75).unwrap(), Vec3::new(-1.0, 1.0, 0.0));
+        // The image circle fills the height, so it ends a quarter of the way across
+        assert_direction(&cam.get_ray(0.75, 0.5).unwrap(), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cam.get_ray(0.8, 0.5).is_none());
+    }
+
+    #[test]
+    fn panorama_directions() {
+        let (lookfrom, lookat, vup) = basis();
+        let cam = EquirectangularCamera::new(lookfrom, lookat, vup, 0.0, 1.0);
+        assert_direction(&cam.get_ray(0.5, 0.5).unwrap(), Vec3::new(-1.0, 0.0, 0.0));
+        assert_direction(&cam.get_ray(0.75, 0.5).unwrap(), Vec3::new(0.0, 0.0, -1.0));
+        assert_direction(&cam.get_ray(0.0, 0.5).unwrap(), Vec3::new(1.0, 0.0, 0.0));
+        assert_direction(&cam.get_ray(0.3, 1.0).unwrap(), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn cube_map_faces() {
+        let (lookfrom, lookat, vup) = basis();
+        let cam = CubeMapCamera::new(lookfrom, lookat, vup, 0.0, 1.0);
+        let centers = [
+            ((1.0 / 6.0, 0.75), Vec3::new(-1.0, 0.0, 0.0)),
+            ((0.5, 0.75), Vec3::new(0.0, 0.0, -1.0)),
+            ((5.0 / 6.0, 0.75), Vec3::new(1.0, 0.0, 0.0)),
+            ((1.0 / 6.0, 0.25), Vec3::new(0.0, 0.0, 1.0)),
+            ((0.5, 0.25), Vec3::new(0.0, 1.0, 0.0)),
+            ((5.0 / 6.0, 0.25), Vec3::new(0.0, -1.0, 0.0)),
+        ];
+        for ((s, t), expected) in centers {
+            assert_direction(&cam.get_ray(s, t).unwrap(), expected);
+        }
+
+        // Neighbouring faces meet along their shared edge
+        assert_direction(
+            &cam.get_ray(1.0 / 3.0, 0.75).unwrap(),
+            Vec3::new(-1.0, 0.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn lens_opening_and_cats_eye() {
+        let (lookfrom, lookat, vup) = basis();
+        let cam = PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            Degrees(40.0),
+            1.5,
+            0.0,
+            5.0,
+            0.0,
+            1.0,
+        )
+        .with_f_number(2.0, 0.4)
+        .with_cats_eye(1.0);
+
+        // Rays leave from within the lens radius of focal length / (2 f-number), and the
+        // barrel clips nothing at the center but about 40% of the opening at the corners
+        let samples = 20000;
+        let mut passed = 0;
+        for _ in 0..samples {
+            let r = cam.get_ray(0.5, 0.5).unwrap();
+            assert!((r.origin() - lookfrom).length() <= 0.1 + 1e-12);
+            if let Some(r) = cam.get_ray(1.0, 1.0) {
+                assert!((r.origin() - lookfrom).length() <= 0.1 + 1e-12);
+                passed += 1;
+            }
+        }
+        let corner = passed as f64 / samples as f64;
+        assert!((corner - 0.391).abs() < 0.02, "{corner}");
+    }
+
+    // Where a ray meets the plane through `point` with normal `normal`
+    fn meet_plane(r: &Ray, point: Point, normal: Vec3) -> Point {
+        let t = Vec3::dot(&(point - r.origin()), &normal) / Vec3::dot(r.direction(), &normal);
+        r.at(t)
+    }
+
+    #[test]
+    fn autofocus_and_shift() {
+        let (lookfrom, lookat, vup) = basis();
+        let sphere = Sphere::new(Point::new(-6.0, 2.0, 3.0), 1.0, test_material());
+        let cam = PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            Degrees(40.0),
+            1.5,
+            0.5,
+            1.0,
+            0.0,
+            1.0,
+        )
+        .autofocus(&sphere, 0.5, 0.5);
+
+        // Every ray through the center converges on the front of the sphere
+        let front = Point::new(-5.0, 2.0, 3.0);
+        for _ in 0..100 {
+            let r = cam.get_ray(0.5, 0.5).unwrap();
+            let p = meet_plane(&r, front, Vec3::new(1.0, 0.0, 0.0));
+            assert!((p - front).length() < 1e-9);
+        }
+
+        // Shifting up by a quarter of the image shows what was a quarter of the way above
+        let pinhole = || {
+            PerspectiveCamera::new(
+                lookfrom,
+                lookat,
+                vup,
+                Degrees(40.0),
+                1.5,
+                0.0,
+                1.0,
+                0.0,
+                1.0,
+            )
+        };
+        let shifted = pinhole().with_shift(0.1, 0.25);
+        let expected = pinhole().get_ray(0.6, 0.75).unwrap();
+        assert_direction(&shifted.get_ray(0.5, 0.5).unwrap(), *expected.direction());
+    }
+
+    #[test]
+    fn tilted_plane_of_focus() {
+        let (lookfrom, lookat, vup) = basis();
+        let camera = |aperture| {
+            PerspectiveCamera::new(
+                lookfrom,
+                lookat,
+                vup,
+                Degrees(40.0),
+                1.5,
+                aperture,
+                4.0,
+                0.0,
+                1.0,
+            )
+            .with_tilt(Degrees(30.0), Degrees(0.0))
+        };
+        let (pinhole, lens) = (camera(0.0), camera(0.8));
+
+        // Tilted forwards about the horizontal axis, the plane leans away at the top
+        let center = Point::new(-3.0, 2.0, 3.0);
+        let tilt = degrees_to_radians(Degrees(30.0)).0;
+        let normal = Vec3::new(tilt.cos(), -tilt.sin(), 0.0);
+        for (s, t) in [(0.5, 0.5), (0.1, 0.9), (0.8, 0.2)] {
+            let sharp = meet_plane(&pinhole.get_ray(s, t).unwrap(), center, normal);
+            for _ in 0..20 {
+                let r = lens.get_ray(s, t).unwrap();
+                assert!((meet_plane(&r, center, normal) - sharp).length() < 1e-9);
+            }
+        }
+    }
 }
@@ -0,0 +1,112 @@
+use crate::microfacet::{fresnel_dielectric, Ggx};
+use crate::spectrum::CHANNEL_WAVELENGTHS;
+use crate::{
+    random_double, Color, HitRecord, Material, MaterialPtr, Onb, Ray, SampledSpectrum,
+    SampledWavelengths, ThinFilm, Vec3,
+};
+
+/// A clear dielectric coat over any other material, such as the lacquer on car paint or
+/// varnished wood. Light either reflects off the coat or reaches the base, which sees the light
+/// the coat lets through. The coat can itself carry a thin film for iridescent finishes.
+pub struct Coated {
+    base: MaterialPtr,
+    ir: f64,
+    distribution: Ggx,
+    film: Option<ThinFilm>,
+}
+
+impl Coated {
+    pub fn new(base: MaterialPtr, ir: f64, roughness: f64) -> Coated {
+        Coated {
+            base,
+            ir,
+            distribution: Ggx::from_roughness(roughness),
+            film: None,
+        }
+    }
+
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Coated {
+        self.film = Some(film);
+        self
+    }
+
+    fn reflectance(&self, rec: &HitRecord, cos_theta: f64, lambda: f64) -> f64 {
+        match &self.film {
+            Some(film) => film.reflectance(film.thickness_at(rec), cos_theta, self.ir, lambda),
+            None => fresnel_dielectric(cos_theta, self.ir),
+        }
+    }
+
+    // Samples a microfacet normal on the coat, returning the reflected direction (if it leaves
+    // the surface), the shadowing weight and the cosine used for the coat's reflectance
+    fn sample_coat(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Onb, Vec3, f64, f64)> {
+        let uvw = Onb::build_from_w(&rec.normal);
+        let wo = uvw.to_local(&-ray_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let wm = self.distribution.sample_visible_normal(&wo);
+        let wi = Vec3::reflect(&-wo, &wm);
+        let weight = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+        Some((uvw, wi, weight, Vec3::dot(&wo, &wm)))
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        if !rec.front_face {
+            return self.base.scatter(ray_in, rec);
+        }
+        let (uvw, wi, weight, cos_theta) = self.sample_coat(ray_in, rec)?;
+        let [r, g, b] = CHANNEL_WAVELENGTHS.map(|lambda| self.reflectance(rec, cos_theta, lambda));
+        let reflectance = Color::new(r, g, b);
+        let average = (r + g + b) / 3.0;
+
+        if random_double(0.0, 1.0) < average {
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            let scattered = Ray::new(rec.p, uvw.local(&wi), ray_in.time());
+            return Some((reflectance * (weight / average), scattered));
+        }
+
+        let (attenuation, scattered) = self.base.scatter(ray_in, rec)?;
+        let transmittance = Color::new(1.0, 1.0, 1.0) - reflectance;
+        Some((transmittance * attenuation / (1.0 - average), scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        if !rec.front_face {
+            return self.base.scatter_spectral(ray_in, rec, lambdas);
+        }
+        let (uvw, wi, weight, cos_theta) = self.sample_coat(ray_in, rec)?;
+        let reflectance =
+            SampledSpectrum::from_fn(lambdas, |lambda| self.reflectance(rec, cos_theta, lambda));
+        let average = reflectance.average();
+
+        if random_double(0.0, 1.0) < average {
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            let scattered = Ray::new(rec.p, uvw.local(&wi), ray_in.time());
+            return Some((reflectance * (weight / average), scattered));
+        }
+
+        let (attenuation, scattered) = self.base.scatter_spectral(ray_in, rec, lambdas)?;
+        let transmittance = reflectance.map(|r| 1.0 - r);
+        Some((transmittance * attenuation / (1.0 - average), scattered))
+    }
+
+    fn passes_through(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.base.passes_through(ray_in, rec)
+    }
+
+    fn walks_inside(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.base.walks_inside(ray_in, rec)
+    }
+}
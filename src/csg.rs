@@ -0,0 +1,192 @@
+use crate::{Aabb, HitRecord, Hittable, HittableObj, Ray, INFINITY};
+
+/// How the two operands of a `Csg` node are combined.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// Everything inside the left operand that is not inside the right operand.
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOp::Union => in_left || in_right,
+            CsgOp::Intersection => in_left && in_right,
+            CsgOp::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Constructive solid geometry over two closed objects.
+///
+/// The operands' intersections along a ray are merged in order of t, tracking whether the ray is
+/// inside each of them. Only the points where the ray enters or leaves the combined solid are
+/// reported, keeping the material of the operand they came from.
+pub struct Csg {
+    op: CsgOp,
+    left: HittableObj,
+    right: HittableObj,
+    bbox: Option<Aabb>,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, left: HittableObj, right: HittableObj) -> Csg {
+        let left_box = left.bounding_box();
+        let right_box = right.bounding_box();
+        let bbox = match op {
+            CsgOp::Union => left_box
+                .zip(right_box)
+                .map(|(a, b)| Aabb::surrounding_box(&a, &b)),
+            CsgOp::Intersection => left_box.or(right_box),
+            CsgOp::Difference => left_box,
+        };
+        Csg {
+            op,
+            left,
+            right,
+            bbox,
+        }
+    }
+
+    pub fn union(left: HittableObj, right: HittableObj) -> Csg {
+        Csg::new(CsgOp::Union, left, right)
+    }
+
+    pub fn intersection(left: HittableObj, right: HittableObj) -> Csg {
+        Csg::new(CsgOp::Intersection, left, right)
+    }
+
+    pub fn difference(left: HittableObj, right: HittableObj) -> Csg {
+        Csg::new(CsgOp::Difference, left, right)
+    }
+
+    fn boundaries(&self, r: &Ray, t_min: f64, t_max: f64, first_only: bool) -> Vec<HitRecord> {
+        // Look past t_max so that the state of each operand at t_min is known from its next hit
+        let left_hits = self.left.hit_all(r, t_min, INFINITY);
+        let right_hits = self.right.hit_all(r, t_min, INFINITY);
+
+        let mut in_left = left_hits.first().is_some_and(|rec| !rec.front_face);
+        let mut in_right = right_hits.first().is_some_and(|rec| !rec.front_face);
+        let mut inside = self.op.inside(in_left, in_right);
+
+        let mut boundaries = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < left_hits.len() || j < right_hits.len() {
+            let from_left =
+                j >= right_hits.len() || (i < left_hits.len() && left_hits[i].t <= right_hits[j].t);
+            let rec = if from_left {
+                i += 1;
+                in_left = left_hits[i - 1].front_face;
+                &left_hits[i - 1]
+            } else {
+                j += 1;
+                in_right = right_hits[j - 1].front_face;
+                &right_hits[j - 1]
+            };
+            if rec.t > t_max {
+                break;
+            }
+
+            let now_inside = self.op.inside(in_left, in_right);
+            if now_inside != inside {
+                inside = now_inside;
+
+                // The normal already faces against the ray, only whether we are entering or
+                // leaving the combined solid can differ from the operand's own
+                let mut boundary = rec.clone();
+                boundary.front_face = now_inside;
+                boundaries.push(boundary);
+                if first_only {
+                    break;
+                }
+            }
+        }
+        boundaries
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if let Some(bbox) = self.bbox {
+            if !bbox.hit(r, t_min, t_max) {
+                return None;
+            }
+        }
+        self.boundaries(r, t_min, t_max, true).pop()
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        self.boundaries(r, t_min, t_max, false)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_material, Point, Sphere, Vec3};
+
+    // Unit spheres centered at x = -0.5 and x = 0.5, overlapping over [-0.5, 0.5]
+    fn csg(op: CsgOp) -> Csg {
+        let material = test_material();
+        Csg::new(
+            op,
+            Box::new(Sphere::new(
+                Point::new(-0.5, 0.0, 0.0),
+                1.0,
+                material.clone(),
+            )),
+            Box::new(Sphere::new(Point::new(0.5, 0.0, 0.0), 1.0, material)),
+        )
+    }
+
+    // Where a ray along the x axis crosses the solid's boundary, and whether it enters there
+    fn boundaries(csg: &Csg, origin: f64) -> Vec<(f64, bool)> {
+        let r = Ray::new(Point::new(origin, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        csg.hit_all(&r, 0.001, INFINITY)
+            .iter()
+            .map(|rec| (rec.p.x(), rec.front_face))
+            .collect()
+    }
+
+    fn assert_boundaries(found: Vec<(f64, bool)>, expected: &[(f64, bool)]) {
+        assert_eq!(found.len(), expected.len(), "{found:?}");
+        for ((x, entering), (expected_x, expected_entering)) in found.iter().zip(expected) {
+            assert!((x - expected_x).abs() < 1e-9, "{found:?}");
+            assert_eq!(entering, expected_entering, "{found:?}");
+        }
+    }
+
+    #[test]
+    fn overlapping_spheres() {
+        let union = csg(CsgOp::Union);
+        assert_boundaries(boundaries(&union, -5.0), &[(-1.5, true), (1.5, false)]);
+        let intersection = csg(CsgOp::Intersection);
+        assert_boundaries(
+            boundaries(&intersection, -5.0),
+            &[(-0.5, true), (0.5, false)],
+        );
+        let difference = csg(CsgOp::Difference);
+        assert_boundaries(
+            boundaries(&difference, -5.0),
+            &[(-1.5, true), (-0.5, false)],
+        );
+    }
+
+    #[test]
+    fn rays_starting_inside() {
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = csg(CsgOp::Union).hit(&r, 0.001, INFINITY).unwrap();
+        assert!((rec.p.x() - 1.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+
+        // The difference is empty from inside the overlap onwards
+        assert!(csg(CsgOp::Difference).hit(&r, 0.001, INFINITY).is_none());
+        assert_boundaries(boundaries(&csg(CsgOp::Difference), -1.0), &[(-0.5, false)]);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{Color, FilterPtr};
+
+/// Accumulates samples into pixels through a reconstruction filter. Each sample is splatted onto
+/// every pixel within the filter's radius, and pixels keep the weighted sum of their samples
+/// alongside the sum of the weights.
+///
+/// Positions are continuous pixel coordinates from the lower left corner of the image, so pixel
+/// (i, j) covers [i, i + 1] x [j, j + 1].
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: FilterPtr,
+    color_sums: Vec<Color>,
+    weight_sums: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: FilterPtr) -> Film {
+        Film {
+            width,
+            height,
+            filter,
+            color_sums: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            weight_sums: vec![0.0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color) {
+        let radius = self.filter.radius();
+        let i0 = (x - 0.5 - radius).ceil().max(0.0) as usize;
+        let j0 = (y - 0.5 - radius).ceil().max(0.0) as usize;
+        let i1 = ((x - 0.5 + radius).floor() as isize).min(self.width as isize - 1);
+        let j1 = ((y - 0.5 + radius).floor() as isize).min(self.height as isize - 1);
+        if i1 < 0 || j1 < 0 {
+            return;
+        }
+
+        for j in j0..=j1 as usize {
+            for i in i0..=i1 as usize {
+                let weight = self
+                    .filter
+                    .evaluate(x - (i as f64 + 0.5), y - (j as f64 + 0.5));
+                self.color_sums[j * self.width + i] += weight * color;
+                self.weight_sums[j * self.width + i] += weight;
+            }
+        }
+    }
+
+    /// The reconstructed color of pixel (i, j), counting rows up from the bottom.
+    pub fn pixel(&self, i: usize, j: usize) -> Color {
+        let weight = self.weight_sums[j * self.width + i];
+        if weight == 0.0 {
+            Color::new(0.0, 0.0, 0.0)
+        } else {
+            self.color_sums[j * self.width + i] / weight
+        }
+    }
+
+    /// Every reconstructed pixel, in rows from the bottom of the image as `draw_buffer_to_ppm`
+    /// takes them.
+    pub fn to_buffer(&self) -> Vec<Vec<Color>> {
+        (0..self.height)
+            .map(|j| (0..self.width).map(|i| self.pixel(i, j)).collect())
+            .collect()
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use crate::PI;
+
+/// A pixel reconstruction filter, weighting each sample by its offset in pixels from the center
+/// of a pixel it lands near.
+pub trait Filter {
+    /// How far from the pixel center, in pixels, samples still contribute.
+    fn radius(&self) -> f64;
+
+    /// The weight of a sample at offset (x, y), which may be negative.
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+pub type FilterPtr = Arc<dyn Filter + Send + Sync>;
+
+/// Weights every sample within the radius equally. At a radius of half a pixel this is plain
+/// averaging of the samples in each pixel.
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> BoxFilter {
+        BoxFilter { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        if x.abs() <= self.radius && y.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Weights fall off linearly to zero at the radius.
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> TentFilter {
+        TentFilter { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+/// A Gaussian with standard deviation `sigma` pixels, shifted down to reach zero at the radius.
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64, sigma: f64) -> GaussianFilter {
+        GaussianFilter { radius, sigma }
+    }
+
+    fn gaussian(&self, x: f64) -> f64 {
+        let edge = (-self.radius * self.radius / (2.0 * self.sigma * self.sigma)).exp();
+        ((-x * x / (2.0 * self.sigma * self.sigma)).exp() - edge).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+/// The Mitchell-Netravali cubic, which trades blurring (`b`) against ringing (`c`). They
+/// recommend b = c = 1/3.
+pub struct MitchellFilter {
+    radius: f64,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64, b: f64, c: f64) -> MitchellFilter {
+        MitchellFilter { radius, b, c }
+    }
+
+    // The cubic over [-2, 2], stretched to the radius
+    fn mitchell(&self, x: f64) -> f64 {
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+        let weight = if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            (-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)
+        };
+        weight / 6.0
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+/// A sinc windowed by a wider sinc, with as many lobes as the radius. Sharper than the others,
+/// with some ringing around edges.
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> LanczosFilter {
+        LanczosFilter { radius }
+    }
+
+    fn lanczos(&self, x: f64) -> f64 {
+        if x.abs() > self.radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> Vec<FilterPtr> {
+        vec![
+            Arc::new(BoxFilter::new(0.5)),
+            Arc::new(TentFilter::new(1.0)),
+            Arc::new(GaussianFilter::new(1.5, 0.5)),
+            Arc::new(MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0)),
+            Arc::new(LanczosFilter::new(3.0)),
+        ]
+    }
+
+    #[test]
+    fn zero_outside_the_radius_and_symmetric() {
+        for filter in filters() {
+            let r = filter.radius();
+            assert_eq!(filter.evaluate(r + 0.01, 0.0), 0.0);
+            assert_eq!(filter.evaluate(0.0, -r - 0.01), 0.0);
+            assert!(filter.evaluate(0.0, 0.0) > 0.0);
+            for &(x, y) in &[(0.3, 0.1), (0.45, -0.2), (0.05, 0.4)] {
+                let w = filter.evaluate(x, y);
+                assert!((filter.evaluate(-x, y) - w).abs() < 1e-12);
+                assert!((filter.evaluate(y, x) - w).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn known_weights() {
+        assert_eq!(BoxFilter::new(0.5).evaluate(0.5, -0.5), 1.0);
+        assert!((TentFilter::new(2.0).evaluate(0.5, 1.0) - 1.5).abs() < 1e-12);
+        assert!((GaussianFilter::new(1.5, 0.5).evaluate(1.5, 0.0)).abs() < 1e-12);
+        let mitchell = MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0);
+        assert!((mitchell.evaluate(0.0, 0.0) - (8.0 / 9.0) * (8.0 / 9.0)).abs() < 1e-12);
+        let lanczos = LanczosFilter::new(3.0);
+        assert!((lanczos.evaluate(0.0, 0.0) - 1.0).abs() < 1e-12);
+        assert!(lanczos.evaluate(1.0, 0.0).abs() < 1e-12);
+        assert!(lanczos.evaluate(0.0, 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn mitchell_weights_sum_to_one_on_the_pixel_grid() {
+        let mitchell = MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0);
+        for &offset in &[0.0, 0.25, 0.5, 0.8] {
+            let sum: f64 = (-3..=3).map(|k| mitchell.mitchell(k as f64 + offset)).sum();
+            assert!((sum - 1.0).abs() < 1e-12, "{sum}");
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{Aabb, HitRecord, Hittable, Image, MaterialPtr, Point, Ray, Vec3};
+
+/// A terrain surface sampled on a regular grid, such as a digital elevation model.
+///
+/// The grid spans `size.x()` along the x axis and `size.z()` along the z axis starting at
+/// `origin`, and samples in [0,1] are scaled by `size.y()`. Every cell is split into two triangles
+/// on the fly, and rays walk the grid through a hierarchy of minimum and maximum heights so that
+/// large empty regions are skipped in a single step.
+pub struct Heightfield {
+    origin: Point,
+    size: Vec3,
+    nx: usize,
+    nz: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    // Level 0 holds the height range of each cell, and every following level merges 2x2 blocks of
+    // the level below
+    mips: Vec<MipLevel>,
+    bbox: Aabb,
+    mat_ptr: MaterialPtr,
+}
+
+struct MipLevel {
+    nx: usize,
+    nz: usize,
+    ranges: Vec<(f64, f64)>,
+}
+
+impl MipLevel {
+    fn range(&self, i: usize, j: usize) -> (f64, f64) {
+        self.ranges[j * self.nx + i]
+    }
+}
+
+impl Heightfield {
+    /// Builds a heightfield from the first channel of `heights`, with image columns running along
+    /// x and rows along z.
+    pub fn new(heights: &Image, origin: Point, size: Vec3, m: MaterialPtr) -> Heightfield {
+        let (nx, nz) = (heights.width(), heights.height());
+        if nx < 2 || nz < 2 {
+            panic!("A heightfield needs at least 2x2 samples");
+        }
+
+        let heights: Vec<f64> = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| origin.y() + size.y() * heights.pixel(i, j)[0])
+            .collect();
+
+        let mut field = Heightfield {
+            origin,
+            size,
+            nx,
+            nz,
+            heights,
+            normals: vec![],
+            mips: vec![],
+            bbox: Aabb::new(origin, origin),
+            mat_ptr: m,
+        };
+        field.normals = field.vertex_normals();
+        field.mips = field.build_mips();
+
+        let (min, max) = field.mips[field.mips.len() - 1].range(0, 0);
+        field.bbox = Aabb::new(
+            Point::new(origin.x(), min, origin.z()),
+            Point::new(origin.x() + size.x(), max, origin.z() + size.z()),
+        );
+        field
+    }
+
+    /// Loads a grayscale heightfield from an 8 or 16-bit PNG.
+    pub fn load_png(
+        path: impl AsRef<Path>,
+        origin: Point,
+        size: Vec3,
+        m: MaterialPtr,
+    ) -> io::Result<Heightfield> {
+        Ok(Heightfield::new(&Image::load_png(path)?, origin, size, m))
+    }
+
+    /// Loads a headerless grid of little-endian 32-bit float heights.
+    pub fn load_raw(
+        path: impl AsRef<Path>,
+        width: usize,
+        height: usize,
+        origin: Point,
+        size: Vec3,
+        m: MaterialPtr,
+    ) -> io::Result<Heightfield> {
+        let image = Image::load_raw_f32(path, width, height, 1)?;
+        Ok(Heightfield::new(&image, origin, size, m))
+    }
+
+    fn cell_width(&self) -> f64 {
+        self.size.x() / (self.nx - 1) as f64
+    }
+
+    fn cell_depth(&self) -> f64 {
+        self.size.z() / (self.nz - 1) as f64
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point {
+        Point::new(
+            self.origin.x() + i as f64 * self.cell_width(),
+            self.heights[j * self.nx + i],
+            self.origin.z() + j as f64 * self.cell_depth(),
+        )
+    }
+
+    // Normals from central differences of the neighbouring samples
+    fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = Vec::with_capacity(self.nx * self.nz);
+        for j in 0..self.nz {
+            for i in 0..self.nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.nz - 1));
+                let dx = self.vertex(i1, j) - self.vertex(i0, j);
+                let dz = self.vertex(i, j1) - self.vertex(i, j0);
+                normals.push(Vec3::cross(&dz, &dx).unit_vector());
+            }
+        }
+        normals
+    }
+
+    fn build_mips(&self) -> Vec<MipLevel> {
+        let (cx, cz) = (self.nx - 1, self.nz - 1);
+        let mut ranges = Vec::with_capacity(cx * cz);
+        for j in 0..cz {
+            for i in 0..cx {
+                let corners = [
+                    self.heights[j * self.nx + i],
+                    self.heights[j * self.nx + i + 1],
+                    self.heights[(j + 1) * self.nx + i],
+                    self.heights[(j + 1) * self.nx + i + 1],
+                ];
+                let min = corners.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                ranges.push((min, max));
+            }
+        }
+
+        let mut mips = vec![MipLevel {
+            nx: cx,
+            nz: cz,
+            ranges,
+        }];
+        loop {
+            let below = &mips[mips.len() - 1];
+            if below.nx == 1 && below.nz == 1 {
+                break;
+            }
+            let (nx, nz) = (below.nx.div_ceil(2), below.nz.div_ceil(2));
+            let mut ranges = Vec::with_capacity(nx * nz);
+            for j in 0..nz {
+                for i in 0..nx {
+                    let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+                    for (ci, cj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (bi, bj) = (2 * i + ci, 2 * j + cj);
+                        if bi < below.nx && bj < below.nz {
+                            let (min, max) = below.range(bi, bj);
+                            range = (range.0.min(min), range.1.max(max));
+                        }
+                    }
+                    ranges.push(range);
+                }
+            }
+            mips.push(MipLevel { nx, nz, ranges });
+        }
+        mips
+    }
+
+    fn hit_cell(&self, r: &Ray, i: usize, j: usize, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest: Option<HitRecord> = None;
+        for tri in [[0, 1, 2], [0, 2, 3]] {
+            let t_max = closest.as_ref().map_or(t_max, |rec| rec.t);
+            let [a, b, c] = tri.map(|k| corners[k]);
+            if let Some(rec) = self.hit_triangle(r, [a, b, c], t_min, t_max) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    // Moller-Trumbore intersection with a triangle of grid vertices, interpolating the vertex
+    // normals across it
+    fn hit_triangle(
+        &self,
+        r: &Ray,
+        vertices: [(usize, usize); 3],
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let [p0, p1, p2] = vertices.map(|(i, j)| self.vertex(i, j));
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let pvec = Vec3::cross(r.direction(), &e2);
+        let det = Vec3::dot(&e1, &pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = r.origin() - p0;
+        let b1 = Vec3::dot(&tvec, &pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+        let qvec = Vec3::cross(&tvec, &e1);
+        let b2 = Vec3::dot(r.direction(), &qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+        let t = Vec3::dot(&e2, &qvec) * inv_det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let [n0, n1, n2] = vertices.map(|(i, j)| self.normals[j * self.nx + i]);
+        let shading_normal = ((1.0 - b1 - b2) * n0 + b1 * n1 + b2 * n2).unit_vector();
+        let (front_face, _) = HitRecord::get_face_normal(r, Vec3::cross(&e2, &e1));
+        let normal = if front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+
+        let p = r.at(t);
+        Some(HitRecord {
+            p,
+            normal,
+            mat_ptr: Arc::clone(&self.mat_ptr),
+            t,
+            u: (p.x() - self.origin.x()) / self.size.x(),
+            v: (p.z() - self.origin.z()) / self.size.z(),
+            dpdu: Vec3::new(self.size.x(), 0.0, 0.0),
+            front_face,
+        })
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t_enter, t_exit) = self.bbox.hit_interval(r, t_min, t_max)?;
+
+        let dir = r.direction();
+        let (cell_w, cell_d) = (self.cell_width(), self.cell_depth());
+        let top = self.mips.len() - 1;
+        let mut level = top;
+        let mut t = t_enter;
+
+        // Each step either descends a level or moves past a cell, so this bounds the walk even
+        // when rounding keeps landing on a cell boundary
+        let max_steps = 4 * (self.nx + self.nz) * (top + 1) + 64;
+        for _ in 0..max_steps {
+            if t > t_exit {
+                break;
+            }
+            let mip = &self.mips[level];
+            let scale = (1usize << level) as f64;
+            let (w, d) = (cell_w * scale, cell_d * scale);
+
+            // Locate the cell at this level that the ray is in, nudging forward so that points on
+            // a boundary land in the cell the ray is moving into
+            let p = r.at(t + 1e-9 * t.abs().max(1.0));
+            let fi = ((p.x() - self.origin.x()) / w).floor();
+            let fj = ((p.z() - self.origin.z()) / d).floor();
+            let i = (fi.max(0.0) as usize).min(mip.nx - 1);
+            let j = (fj.max(0.0) as usize).min(mip.nz - 1);
+
+            // Where the ray leaves the cell
+            let x0 = self.origin.x() + i as f64 * w;
+            let z0 = self.origin.z() + j as f64 * d;
+            let tx = if dir.x() > 0.0 {
+                (x0 + w - r.origin().x()) / dir.x()
+            } else if dir.x() < 0.0 {
+                (x0 - r.origin().x()) / dir.x()
+            } else {
+                f64::INFINITY
+            };
+            let tz = if dir.z() > 0.0 {
+                (z0 + d - r.origin().z()) / dir.z()
+            } else if dir.z() < 0.0 {
+                (z0 - r.origin().z()) / dir.z()
+            } else {
+                f64::INFINITY
+            };
+            let t_cell_exit = tx.min(tz).min(t_exit).max(t);
+
+            // Skip the whole cell if the ray passes entirely above or below its heights
+            let (y0, y1) = (r.at(t).y(), r.at(t_cell_exit).y());
+            let (min, max) = mip.range(i, j);
+            if y0.max(y1) < min || y0.min(y1) > max {
+                t = t_cell_exit;
+                level = (level + 1).min(top);
+                if t_cell_exit >= t_exit {
+                    break;
+                }
+                continue;
+            }
+
+            if level > 0 {
+                level -= 1;
+                continue;
+            }
+
+            if let Some(rec) = self.hit_cell(r, i, j, t_min, t_max) {
+                return Some(rec);
+            }
+            if t_cell_exit >= t_exit {
+                break;
+            }
+            t = t_cell_exit;
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random_double, test_material};
+
+    // Rolling hills on a grid whose sides are not powers of two, so the coarser levels have
+    // partial blocks
+    fn hills() -> Heightfield {
+        let (nx, nz) = (37, 23);
+        let data = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| 0.5 + 0.25 * (0.4 * i as f64).sin() * (0.3 * j as f64).cos())
+            .collect();
+        Heightfield::new(
+            &Image::new(nx, nz, 1, data),
+            Point::new(-4.0, -1.0, -3.0),
+            Vec3::new(8.0, 2.0, 6.0),
+            test_material(),
+        )
+    }
+
+    // Tests every cell, without the hierarchy
+    fn brute_force(field: &Heightfield, r: &Ray) -> Option<HitRecord> {
+        let mut closest: Option<HitRecord> = None;
+        for j in 0..field.nz - 1 {
+            for i in 0..field.nx - 1 {
+                let t_max = closest.as_ref().map_or(f64::INFINITY, |rec| rec.t);
+                if let Some(rec) = field.hit_cell(r, i, j, 0.001, t_max) {
+                    closest = Some(rec);
+                }
+            }
+        }
+        closest
+    }
+
+    #[test]
+    fn flat_field() {
+        let field = Heightfield::new(
+            &Image::new(2, 2, 1, vec![0.5; 4]),
+            Point::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 4.0, 2.0),
+            test_material(),
+        );
+        let r = Ray::new(Point::new(0.5, 5.0, 1.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let rec = field.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.75).abs() < 1e-9);
+
+        // Beside the field
+        let r = Ray::new(Point::new(2.5, 5.0, 1.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(field.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn hierarchy_matches_every_cell() {
+        let field = hills();
+        for _ in 0..2000 {
+            let origin = Point::new(
+                random_double(-6.0, 6.0),
+                random_double(-0.5, 3.0),
+                random_double(-5.0, 5.0),
+            );
+            let target = Point::new(
+                random_double(-4.0, 4.0),
+                random_double(-1.0, 1.0),
+                random_double(-3.0, 3.0),
+            );
+            let r = Ray::new(origin, target - origin, 0.0);
+            let expected = brute_force(&field, &r).map(|rec| rec.t);
+            let found = field.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t);
+            match (expected, found) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-9, "{a} {b}"),
+                (None, None) => (),
+                _ => panic!("walk and brute force disagree: {expected:?} {found:?}"),
+            }
+        }
+    }
+}
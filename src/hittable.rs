@@ -1,15 +1,22 @@
 use std::sync::Arc;
 
-use crate::{Material, Point, Ray, Vec3};
+use crate::{Aabb, Material, Point, Ray, Vec3, PI};
 
 pub type MaterialPtr = Arc<dyn Material + Send + Sync>;
 pub type HittableObj = Box<dyn Hittable + Send + Sync>;
+pub type HittablePtr = Arc<dyn Hittable + Send + Sync>;
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub p: Point,
     pub normal: Vec3,
     pub mat_ptr: MaterialPtr,
-    t: f64,
+    pub(crate) t: f64,
+    pub u: f64,
+    pub v: f64,
+    /// The direction in which `u` increases along the surface, not necessarily normalized. It
+    /// orients tangent space for normal and bump mapping.
+    pub dpdu: Vec3,
     pub front_face: bool,
 }
 
@@ -27,6 +34,49 @@ impl HitRecord {
 
 pub trait Hittable {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    /// The box enclosing the object, or `None` if it is unbounded (such as an infinite plane).
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Every intersection along the ray within the interval, in increasing order of t. For closed
+    /// objects these alternate between entering (`front_face`) and exiting the solid.
+    ///
+    /// The default implementation repeatedly calls `hit` just past the previous intersection.
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        const MAX_HITS: usize = 64;
+        let mut hits = vec![];
+        let mut t = t_min;
+        while hits.len() < MAX_HITS {
+            match self.hit(r, t, t_max) {
+                Some(rec) => {
+                    t = rec.t + 1e-7 * rec.t.abs().max(1.0);
+                    hits.push(rec);
+                }
+                None => break,
+            }
+        }
+        hits
+    }
+}
+
+/// The closest hit on `object` that the ray does not pass through, skipping the cut-outs of
+/// materials such as `AlphaMask`.
+pub(crate) fn hit_opaque<T: Hittable + ?Sized>(
+    object: &T,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    const MAX_LAYERS: usize = 64;
+    let mut t_min = t_min;
+    for _ in 0..MAX_LAYERS {
+        let rec = object.hit(r, t_min, t_max)?;
+        if !rec.mat_ptr.passes_through(r, &rec) {
+            return Some(rec);
+        }
+        t_min = rec.t + 1e-7 * rec.t.abs().max(1.0);
+    }
+    None
 }
 
 pub struct Sphere {
@@ -43,14 +93,29 @@ impl Sphere {
             mat_ptr: m,
         }
     }
-}
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin() - self.center;
+    fn get_sphere_uv(p: &Point) -> (f64, f64) {
+        // p: a given point on the sphere of radius one, centered at the origin.
+        // u: returned value [0,1] of angle around the Y axis from X=-1.
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // Shared with MovingSphere, which only knows its center once the ray's time is known
+    fn hit_at(
+        center: Point,
+        radius: f64,
+        mat_ptr: &MaterialPtr,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let oc = r.origin() - center;
         let a = r.direction().length_squared();
         let half_b = Vec3::dot(&oc, r.direction());
-        let c = oc.length_squared() - self.radius * self.radius;
+        let c = oc.length_squared() - radius * radius;
 
         let discriminant = half_b * half_b - a * c;
         if discriminant < 0.0 {
@@ -67,18 +132,136 @@ impl Hittable for Sphere {
             }
         }
 
-        let t = root;
+        Some(Sphere::hit_record(center, radius, mat_ptr, r, root))
+    }
+
+    fn hit_record(center: Point, radius: f64, mat_ptr: &MaterialPtr, r: &Ray, t: f64) -> HitRecord {
         let p = r.at(t);
-        let outward_normal = (p - self.center) / self.radius;
+        let outward_normal = (p - center) / radius;
         let (front_face, normal) = HitRecord::get_face_normal(r, outward_normal);
+        let (u, v) = Sphere::get_sphere_uv(&outward_normal);
 
-        Some(HitRecord {
+        HitRecord {
             p,
             normal,
-            mat_ptr: Arc::clone(&self.mat_ptr),
+            mat_ptr: Arc::clone(mat_ptr),
             t,
+            u,
+            v,
+            dpdu: Vec3::new(outward_normal.z(), 0.0, -outward_normal.x()),
             front_face,
-        })
+        }
+    }
+
+    fn hit_all_at(
+        center: Point,
+        radius: f64,
+        mat_ptr: &MaterialPtr,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Vec<HitRecord> {
+        let oc = r.origin() - center;
+        let a = r.direction().length_squared();
+        let half_b = Vec3::dot(&oc, r.direction());
+        let c = oc.length_squared() - radius * radius;
+
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return vec![];
+        }
+        let sqrtd = discriminant.sqrt();
+
+        [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+            .into_iter()
+            .filter(|root| t_min <= *root && *root <= t_max)
+            .map(|root| Sphere::hit_record(center, radius, mat_ptr, r, root))
+            .collect()
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        Sphere::hit_at(self.center, self.radius, &self.mat_ptr, r, t_min, t_max)
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        Sphere::hit_all_at(self.center, self.radius, &self.mat_ptr, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let offset = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - offset, self.center + offset))
+    }
+}
+
+/// A sphere whose center moves linearly from `center0` at `time0` to `center1` at `time1`.
+pub struct MovingSphere {
+    center0: Point,
+    center1: Point,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    mat_ptr: MaterialPtr,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point,
+        center1: Point,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        m: MaterialPtr,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            mat_ptr: m,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point {
+        // A zero-length interval would divide by zero, so the sphere simply stays put
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        self.center0
+            + ((time - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        Sphere::hit_at(
+            self.center(r.time()),
+            self.radius,
+            &self.mat_ptr,
+            r,
+            t_min,
+            t_max,
+        )
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        Sphere::hit_all_at(
+            self.center(r.time()),
+            self.radius,
+            &self.mat_ptr,
+            r,
+            t_min,
+            t_max,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let offset = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = Aabb::new(self.center0 - offset, self.center0 + offset);
+        let box1 = Aabb::new(self.center1 - offset, self.center1 + offset);
+        Some(Aabb::surrounding_box(&box0, &box1))
     }
 }
 
@@ -98,23 +281,47 @@ impl HittableList {
     pub fn clear(&mut self) {
         self.objects.clear();
     }
-}
 
-impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut temp_rec: Option<HitRecord> = None;
+    pub fn into_objects(self) -> Vec<HittableObj> {
+        self.objects
+    }
+
+    /// Like `hit`, also returning the index of the object hit, in the order they were added.
+    pub fn hit_object(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(usize, HitRecord)> {
+        let mut temp_rec: Option<(usize, HitRecord)> = None;
         let mut closest_so_far = t_max;
 
-        for object in self.objects.iter() {
-            match object.as_ref().hit(r, t_min, closest_so_far) {
-                Some(rec) => {
-                    closest_so_far = rec.t;
-                    temp_rec = Some(rec);
-                }
-                None => (),
+        for (index, object) in self.objects.iter().enumerate() {
+            if let Some(rec) = hit_opaque(object.as_ref(), r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                temp_rec = Some((index, rec));
             }
         }
 
         temp_rec
     }
 }
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.hit_object(r, t_min, t_max).map(|(_, rec)| rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut output_box: Option<Aabb> = None;
+        for object in self.objects.iter() {
+            let bbox = object.bounding_box()?;
+            output_box = Some(match output_box {
+                Some(output_box) => Aabb::surrounding_box(&output_box, &bbox),
+                None => bbox,
+            });
+        }
+        output_box
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// A grid of floating point samples with one or more channels, stored row by row from the top
+/// of the image.
+pub struct Image {
+    width: usize,
+    height: usize,
+    channels: usize,
+    data: Vec<f64>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, channels: usize, data: Vec<f64>) -> Image {
+        assert_eq!(
+            data.len(),
+            width * height * channels,
+            "Image data does not match its dimensions"
+        );
+        Image {
+            width,
+            height,
+            channels,
+            data,
+        }
+    }
+
+    /// Loads an 8 or 16-bit PNG, normalizing each sample to [0,1]. Paletted images are expanded
+    /// to RGB.
+    pub fn load_png(path: impl AsRef<Path>) -> io::Result<Image> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let bytes = &buf[..info.buffer_size()];
+
+        let data = match info.bit_depth {
+            png::BitDepth::Sixteen => bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as f64 / 65535.0)
+                .collect(),
+            png::BitDepth::Eight => bytes.iter().map(|&b| b as f64 / 255.0).collect(),
+            bit_depth => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unsupported PNG bit depth {bit_depth:?}"),
+                ))
+            }
+        };
+
+        Ok(Image::new(
+            info.width as usize,
+            info.height as usize,
+            info.color_type.samples(),
+            data,
+        ))
+    }
+
+    /// Loads a headerless grid of little-endian 32-bit floats with the given dimensions.
+    pub fn load_raw_f32(
+        path: impl AsRef<Path>,
+        width: usize,
+        height: usize,
+        channels: usize,
+    ) -> io::Result<Image> {
+        let mut bytes = vec![];
+        BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+        if bytes.len() != width * height * channels * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Expected {} bytes for a {width}x{height}x{channels} float grid, found {}",
+                    width * height * channels * 4,
+                    bytes.len()
+                ),
+            ));
+        }
+
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        Ok(Image::new(width, height, channels, data))
+    }
+
+    /// Saves a one or three channel image as a PFM, a minimal floating point format that keeps
+    /// values outside [0,1].
+    pub fn save_pfm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let kind = match self.channels {
+            1 => "Pf",
+            3 => "PF",
+            channels => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("PFM images have 1 or 3 channels, not {channels}"),
+                ))
+            }
+        };
+
+        // A negative scale marks the data as little-endian. Rows run from the bottom up.
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{kind}\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.data.chunks_exact(self.width * self.channels).rev() {
+            for &sample in row {
+                out.write_all(&(sample as f32).to_le_bytes())?;
+            }
+        }
+        out.flush()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// The samples of the pixel in column `x` and row `y`.
+    pub fn pixel(&self, x: usize, y: usize) -> &[f64] {
+        let start = (y * self.width + x) * self.channels;
+        &self.data[start..start + self.channels]
+    }
+}
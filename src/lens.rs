@@ -0,0 +1,406 @@
+use crate::{random_double, Camera, CameraBasis, Point, Ray, Vec3};
+
+/// One surface of a lens prescription, listed from the front of the lens (facing the scene) to
+/// the back (facing the film). Measurements are in millimetres.
+#[derive(Debug, Copy, Clone)]
+pub struct LensElement {
+    /// Radius of curvature, positive when the surface bulges towards the scene. Zero marks the
+    /// aperture stop, which is flat.
+    pub radius: f64,
+    /// Distance along the axis to the next surface, or to the film for the last one.
+    pub thickness: f64,
+    /// Index of refraction of the glass behind the surface, or 1 (or 0) for air.
+    pub ior: f64,
+    /// Diameter of the surface's clear opening.
+    pub aperture: f64,
+}
+
+impl LensElement {
+    pub fn new(radius: f64, thickness: f64, ior: f64, aperture: f64) -> LensElement {
+        LensElement {
+            radius,
+            thickness,
+            ior,
+            aperture,
+        }
+    }
+
+    /// A 50mm f/2 double Gauss design, the classic normal lens.
+    pub fn double_gauss_50mm() -> Vec<LensElement> {
+        vec![
+            LensElement::new(29.475, 3.76, 1.67, 25.2),
+            LensElement::new(84.83, 0.12, 1.0, 25.2),
+            LensElement::new(19.275, 4.025, 1.67, 23.0),
+            LensElement::new(40.77, 3.275, 1.699, 23.0),
+            LensElement::new(12.75, 5.705, 1.0, 18.0),
+            LensElement::new(0.0, 4.5, 0.0, 17.1),
+            LensElement::new(-14.495, 1.18, 1.603, 17.0),
+            LensElement::new(40.77, 6.065, 1.658, 20.0),
+            LensElement::new(-20.385, 0.19, 1.0, 20.0),
+            LensElement::new(437.065, 3.22, 1.717, 20.0),
+            LensElement::new(-39.73, 0.0, 1.0, 20.0),
+        ]
+    }
+
+    fn medium_ior(&self) -> f64 {
+        if self.ior == 0.0 {
+            1.0
+        } else {
+            self.ior
+        }
+    }
+}
+
+// Bounds on the rear element's plane that rays from a range of film positions get through
+#[derive(Debug, Copy, Clone)]
+struct PupilBounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl PupilBounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+}
+
+/// A camera that traces rays through the actual surfaces of a lens, giving the distortion,
+/// vignetting and focus breathing of the design. The scene is measured in metres.
+///
+/// Sampling the whole rear element would waste most rays on paths the lens blocks, so the
+/// exit pupil, the part of the rear element that light gets through, is bounded up front for
+/// rings of the film.
+pub struct RealisticCamera {
+    basis: CameraBasis,
+    elements: Vec<LensElement>,
+    // Distance of each surface's vertex from the film
+    vertex_z: Vec<f64>,
+    film_width: f64,
+    film_height: f64,
+    exit_pupils: Vec<Option<PupilBounds>>,
+    max_pupil_area: f64,
+    time0: f64,
+    time1: f64,
+}
+
+impl RealisticCamera {
+    const MM_PER_UNIT: f64 = 1000.0;
+    const FOCUS_ITERATIONS: usize = 8;
+    const PUPIL_INTERVALS: usize = 64;
+    const PUPIL_GRID: usize = 64;
+    // Film radii tried for each point of the grid, evenly spaced from the inner to the outer edge
+    // of an interval
+    const PUPIL_RADII: usize = 4;
+
+    /// `film_diagonal` is in millimetres (43.3 for full frame) and `focus_dist` in scene units
+    /// from the film. The last element's thickness is replaced to bring that distance into focus.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lookfrom: Point,
+        lookat: Point,
+        vup: Vec3,
+        elements: Vec<LensElement>,
+        film_diagonal: f64,
+        aspect_ratio: f64,
+        focus_dist: f64,
+        time0: f64,
+        time1: f64,
+    ) -> RealisticCamera {
+        let film_height = film_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let mut camera = RealisticCamera {
+            basis: CameraBasis::new(lookfrom, lookat, vup),
+            vertex_z: vec![0.0; elements.len()],
+            elements,
+            film_width: aspect_ratio * film_height,
+            film_height,
+            exit_pupils: Vec::new(),
+            max_pupil_area: 0.0,
+            time0,
+            time1,
+        };
+        camera.focus(focus_dist * RealisticCamera::MM_PER_UNIT);
+        camera.exit_pupils = (0..RealisticCamera::PUPIL_INTERVALS)
+            .map(|i| {
+                let n = RealisticCamera::PUPIL_INTERVALS as f64;
+                let half_diagonal = film_diagonal / 2.0;
+                camera.bound_exit_pupil(
+                    half_diagonal * i as f64 / n,
+                    half_diagonal * (i + 1) as f64 / n,
+                )
+            })
+            .collect();
+        camera.max_pupil_area = camera
+            .exit_pupils
+            .iter()
+            .flatten()
+            .map(PupilBounds::area)
+            .fold(0.0, f64::max);
+        camera
+    }
+
+    fn place_elements(&mut self) {
+        let mut z = 0.0;
+        for (element, vertex_z) in self.elements.iter().zip(&mut self.vertex_z).rev() {
+            z += element.thickness;
+            *vertex_z = z;
+        }
+    }
+
+    // Moves the film until a point on the axis at `distance` from it comes to a focus. The lens
+    // moving changes the distance, so this repeats until it settles.
+    fn focus(&mut self, distance: f64) {
+        self.place_elements();
+        let front = self.elements[0];
+        for _ in 0..RealisticCamera::FOCUS_ITERATIONS {
+            let object = Point::new(0.0, 0.0, distance);
+            let toward = Point::new(0.01 * front.aperture, 0.0, self.vertex_z[0]);
+            let Some((origin, direction)) = self.trace(object, toward - object, false) else {
+                return;
+            };
+            if direction.x().abs() < 1e-12 {
+                return;
+            }
+
+            let crossing = origin.z() - origin.x() / direction.x() * direction.z();
+            if let Some(rear) = self.elements.last_mut() {
+                rear.thickness -= crossing;
+            }
+            self.place_elements();
+        }
+    }
+
+    // Traces a ray in lens space, with the film at z = 0 and the scene towards +z, through every
+    // surface: outwards from the film, or inwards from the scene. `None` if it is blocked.
+    fn trace(&self, origin: Point, direction: Vec3, from_film: bool) -> Option<(Point, Vec3)> {
+        let (mut origin, mut direction) = (origin, direction);
+        let n = self.elements.len();
+        for k in 0..n {
+            let i = if from_film { n - 1 - k } else { k };
+            let element = &self.elements[i];
+            let z = self.vertex_z[i];
+
+            let (t, normal) = if element.radius == 0.0 {
+                ((z - origin.z()) / direction.z(), None)
+            } else {
+                let center = Point::new(0.0, 0.0, z - element.radius);
+                let oc = origin - center;
+                let a = direction.length_squared();
+                let half_b = Vec3::dot(&oc, &direction);
+                let c = oc.length_squared() - element.radius * element.radius;
+                let discriminant = half_b * half_b - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let closer = (direction.z() > 0.0) != (element.radius > 0.0);
+                let t = if closer {
+                    (-half_b - discriminant.sqrt()) / a
+                } else {
+                    (-half_b + discriminant.sqrt()) / a
+                };
+                (t, Some(center))
+            };
+            if t.is_nan() || t <= 0.0 {
+                return None;
+            }
+
+            let p = origin + t * direction;
+            let half_aperture = element.aperture / 2.0;
+            if p.x() * p.x() + p.y() * p.y() > half_aperture * half_aperture {
+                return None;
+            }
+            origin = p;
+
+            if let Some(center) = normal {
+                let outside = if i == 0 {
+                    1.0
+                } else {
+                    self.elements[i - 1].medium_ior()
+                };
+                let (eta_i, eta_t) = if from_film {
+                    (element.medium_ior(), outside)
+                } else {
+                    (outside, element.medium_ior())
+                };
+                let mut normal = (p - center).unit_vector();
+                if Vec3::dot(&normal, &direction) > 0.0 {
+                    normal = -normal;
+                }
+                direction = refract(&direction.unit_vector(), &normal, eta_i / eta_t)?;
+            }
+        }
+        Some((origin, direction))
+    }
+
+    fn bound_exit_pupil(&self, r0: f64, r1: f64) -> Option<PupilBounds> {
+        let rear = self.elements.last()?;
+        let rear_radius = rear.aperture / 2.0;
+        let rear_z = *self.vertex_z.last()?;
+        let grid = RealisticCamera::PUPIL_GRID;
+        let radii = RealisticCamera::PUPIL_RADII;
+        let step = 2.0 * rear_radius / grid as f64;
+
+        let mut bounds: Option<PupilBounds> = None;
+        for i in 0..grid {
+            for j in 0..grid {
+                let px = -rear_radius + (i as f64 + 0.5) * step;
+                let py = -rear_radius + (j as f64 + 0.5) * step;
+                let rear_point = Point::new(px, py, rear_z);
+                let passes = (0..radii).any(|k| {
+                    let film = Point::new(r0 + (r1 - r0) * k as f64 / (radii - 1) as f64, 0.0, 0.0);
+                    self.trace(film, rear_point - film, true).is_some()
+                });
+                if !passes {
+                    continue;
+                }
+                bounds = Some(match bounds {
+                    Some(b) => PupilBounds {
+                        min: (b.min.0.min(px), b.min.1.min(py)),
+                        max: (b.max.0.max(px), b.max.1.max(py)),
+                    },
+                    None => PupilBounds {
+                        min: (px, py),
+                        max: (px, py),
+                    },
+                });
+            }
+        }
+
+        // Grow by a grid step to cover passing points between the samples
+        bounds.map(|b| PupilBounds {
+            min: (b.min.0 - step, b.min.1 - step),
+            max: (b.max.0 + step, b.max.1 + step),
+        })
+    }
+}
+
+// Refracts a unit direction through a surface with `normal` facing against it, or `None` on
+// total internal reflection
+fn refract(direction: &Vec3, normal: &Vec3, etai_over_etat: f64) -> Option<Vec3> {
+    let cos_i = -Vec3::dot(direction, normal);
+    let sin2_t = etai_over_etat * etai_over_etat * (1.0 - cos_i * cos_i).max(0.0);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(etai_over_etat * *direction + (etai_over_etat * cos_i - cos_t) * *normal)
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        // The lens turns the image upside down, so the film is read rotated half a turn
+        let film = Point::new(
+            -(s - 0.5) * self.film_width,
+            -(t - 0.5) * self.film_height,
+            0.0,
+        );
+        let r = (film.x() * film.x() + film.y() * film.y()).sqrt();
+        let half_diagonal = 0.5 * self.film_width.hypot(self.film_height);
+        let interval = ((r / half_diagonal * RealisticCamera::PUPIL_INTERVALS as f64) as usize)
+            .min(RealisticCamera::PUPIL_INTERVALS - 1);
+        let pupil = self.exit_pupils[interval]?;
+
+        // The bounds are for film points along +x, so rotate them around to this one
+        let x = random_double(pupil.min.0, pupil.max.0);
+        let y = random_double(pupil.min.1, pupil.max.1);
+        let (sin_phi, cos_phi) = if r > 0.0 {
+            (film.y() / r, film.x() / r)
+        } else {
+            (0.0, 1.0)
+        };
+        let rear = Point::new(
+            x * cos_phi - y * sin_phi,
+            x * sin_phi + y * cos_phi,
+            *self.vertex_z.last()?,
+        );
+        let direction = rear - film;
+
+        // Sampling a smaller pupil gathers less light, and light reaches the film at an angle
+        // spread over more of it (the cos⁴ law). Both darken the image, so rays are dropped in
+        // proportion.
+        let cos_theta = direction.z() / direction.length();
+        let weight = pupil.area() / self.max_pupil_area * cos_theta.powi(4);
+        if random_double(0.0, 1.0) >= weight {
+            return None;
+        }
+
+        let (origin, direction) = self.trace(film, direction, true)?;
+        Some(Ray::new(
+            self.basis.origin() + self.basis.local(&(origin / RealisticCamera::MM_PER_UNIT)),
+            self.basis.local(&direction),
+            random_double(self.time0, self.time1),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Looking down -z from the origin, so right is +x and up is +y
+    fn camera(focus_dist: f64) -> RealisticCamera {
+        RealisticCamera::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            LensElement::double_gauss_50mm(),
+            43.3,
+            1.5,
+            focus_dist,
+            0.0,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn refraction_follows_snells_law() {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let straight = refract(&Vec3::new(0.0, 0.0, -1.0), &normal, 1.0 / 1.5).unwrap();
+        assert!((straight - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+
+        let incoming = Vec3::new(0.6, 0.0, -0.8);
+        let bent = refract(&incoming, &normal, 1.0 / 1.5).unwrap();
+        assert!((bent.length() - 1.0).abs() < 1e-12);
+        assert!((bent.x() - 0.6 / 1.5).abs() < 1e-12);
+
+        // Past the critical angle leaving glass
+        assert!(refract(&Vec3::new(0.8, 0.0, -0.6), &normal, 1.5).is_none());
+    }
+
+    #[test]
+    fn focused_points_converge_on_the_film() {
+        for focus_dist in [1.0, 5.0] {
+            let cam = camera(focus_dist);
+            let object = Point::new(0.0, 0.0, focus_dist * RealisticCamera::MM_PER_UNIT);
+            for height in [0.5, 1.0, 2.0] {
+                let toward = Point::new(height, 0.0, cam.vertex_z[0]);
+                let (origin, direction) = cam.trace(object, toward - object, false).unwrap();
+                let x = origin.x() - origin.z() / direction.z() * direction.x();
+                assert!(x.abs() < 0.01, "{focus_dist} {height}: {x}");
+            }
+        }
+    }
+
+    #[test]
+    fn rays_leave_through_the_front_of_the_lens() {
+        let cam = camera(2.0);
+        let mut seen = [0; 3];
+        for _ in 0..2000 {
+            for (k, (s, t)) in [(0.5, 0.5), (0.9, 0.5), (0.5, 0.1)].into_iter().enumerate() {
+                let Some(r) = cam.get_ray(s, t) else {
+                    continue;
+                };
+                seen[k] += 1;
+                // Within the lens barrel, and heading out with the image the right way up
+                assert!(r.origin().length() < 0.1);
+                let d = r.direction().unit_vector();
+                assert!(d.z() < 0.0);
+                match k {
+                    0 => assert!(d.x().abs() < 0.05 && d.y().abs() < 0.05),
+                    1 => assert!(d.x() > 0.0),
+                    _ => assert!(d.y() < 0.0),
+                }
+            }
+        }
+        assert!(seen.iter().all(|&n| n > 0), "{seen:?}");
+    }
+}
@@ -1,7 +1,6 @@
 #[macro_use]
 extern crate impl_ops;
 
-use rand;
 use std::io;
 
 mod vec3;
@@ -15,18 +14,125 @@ pub use ray::Ray;
 mod color;
 
 mod hittable;
-pub use hittable::{HitRecord, Hittable, HittableList, HittableObj, MaterialPtr, Sphere};
+pub use hittable::{
+    HitRecord, Hittable, HittableList, HittableObj, HittablePtr, MaterialPtr, MovingSphere, Sphere,
+};
 
+mod aabb;
+pub use aabb::Aabb;
+
+mod bvh;
+pub use bvh::BvhNode;
+
+mod onb;
+pub use onb::Onb;
+
+mod aarect;
+pub use aarect::{XyRect, XzRect, YzRect};
+
+mod quad;
+pub use quad::{Cuboid, Disk, Plane, Quad};
+
+mod quadric;
+pub use quadric::{Cone, Cylinder, Paraboloid, Torus};
+
+mod image;
+pub use image::Image;
+
+mod heightfield;
+pub use heightfield::Heightfield;
+
+mod sdf;
+pub use sdf::{
+    Mandelbulb, Repeat, Sdf, SdfCapsule, SdfPtr, SdfRoundedBox, SdfTorus, SignedDistance,
+    SmoothSubtraction, SmoothUnion,
+};
+
+mod csg;
+pub use csg::{Csg, CsgOp};
+
+mod transform;
+pub use transform::{Keyframe, Keyframed, Transform, Transformed};
+
+#[derive(Debug, Copy, Clone)]
 pub struct Degrees(pub f64);
+#[derive(Debug, Copy, Clone)]
 pub struct Radians(pub f64);
+mod aperture;
+pub use aperture::Aperture;
+
+mod filter;
+pub use filter::{
+    BoxFilter, Filter, FilterPtr, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter,
+};
+
+mod film;
+pub use film::Film;
+
+mod aov;
+pub use aov::{Aov, AovFilm, AovSample, MaterialIds};
+
 mod camera;
-pub use camera::Camera;
+pub use camera::{
+    Camera, CameraBasis, CameraPtr, CubeMapCamera, EquirectangularCamera, FisheyeCamera,
+    OrthographicCamera, PerspectiveCamera,
+};
+
+mod lens;
+pub use lens::{LensElement, RealisticCamera};
+
+mod stereo;
+pub use stereo::{OdsCamera, StereoCamera};
 
 mod material;
-pub use material::{Dielectric, Lambertian, Material, Metal};
+pub use material::{Dielectric, Dispersion, Lambertian, Material, Metal};
+
+mod microfacet;
+pub use microfacet::{RoughConductor, RoughDielectric};
+
+mod spectrum;
+pub use spectrum::{
+    blackbody, blackbody_rgb, cie_xyz, SampledSpectrum, SampledWavelengths, LAMBDA_MAX, LAMBDA_MIN,
+    N_SPECTRUM_SAMPLES,
+};
+
+mod texture;
+pub use texture::{CheckerTexture, ImageTexture, SolidColor, Texture, TexturePtr};
+
+mod principled;
+pub use principled::Principled;
+
+mod thinfilm;
+pub use thinfilm::ThinFilm;
+
+mod coated;
+pub use coated::Coated;
+
+mod normalmap;
+pub use normalmap::{BumpMapped, NormalMapped};
 
-pub const INFINITY: f64 = std::f64::INFINITY;
-pub const PI: f64 = 3.1415926535897932385;
+mod alphamask;
+pub use alphamask::AlphaMask;
+
+mod mix;
+pub use mix::{MixMaterial, TwoSided};
+
+mod perlin;
+pub use perlin::Perlin;
+
+mod phase;
+pub use phase::{
+    DoubleHenyeyGreenstein, HenyeyGreenstein, Isotropic, PhaseFunction, PhasePtr, Rayleigh,
+};
+
+mod medium;
+pub use medium::{ConstantMedium, HeterogeneousMedium, VoxelGrid};
+
+mod subsurface;
+pub use subsurface::Subsurface;
+
+pub const INFINITY: f64 = f64::INFINITY;
+pub const PI: f64 = std::f64::consts::PI;
 
 pub fn degrees_to_radians(degrees: Degrees) -> Radians {
     Radians(degrees.0 * PI / 180.0)
@@ -43,13 +149,12 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x > max {
         return max;
     }
-    return x;
+    x
 }
 
 pub fn draw_buffer_to_ppm(buffer: Vec<Vec<Color>>, samples_per_pixel: u64) {
     for j in (0..buffer.len()).rev() {
-        for i in 0..buffer[0].len() {
-            let pixel_color = buffer[j][i];
+        for pixel_color in buffer[j].iter() {
             let stdout = io::stdout();
             let mut handle = stdout.lock();
 
@@ -57,3 +162,9 @@ pub fn draw_buffer_to_ppm(buffer: Vec<Vec<Color>>, samples_per_pixel: u64) {
         }
     }
 }
+
+/// A plain gray material for objects in tests.
+#[cfg(test)]
+pub(crate) fn test_material() -> MaterialPtr {
+    std::sync::Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+}
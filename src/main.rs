@@ -1,33 +1,150 @@
+use std::fs;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use threadpool::ThreadPool;
 
 use rt::Hittable;
 use rtweekend as rt;
 
-pub fn ray_color(r: &rt::Ray, world: &rt::HittableList, depth: u64) -> rt::Color {
+// Steps of random walks under surfaces come out of their own budget, so that a long walk does
+// not use up the bounces of the path. Once that runs out they count as bounces.
+const MAX_WALK_STEPS: u64 = 256;
+
+fn next_depth(r: &rt::Ray, rec: &rt::HitRecord, depth: u64, walk_steps: u64) -> (u64, u64) {
+    if walk_steps > 0 && rec.mat_ptr.walks_inside(r, rec) {
+        (depth, walk_steps - 1)
+    } else {
+        (depth - 1, walk_steps)
+    }
+}
+
+pub fn ray_color(r: &rt::Ray, world: &rt::BvhNode, depth: u64, walk_steps: u64) -> rt::Color {
     // If we've exceeded the ray bounce limit, no more light is gathered.
-    if depth <= 0 {
+    if depth == 0 {
         return rt::Color::new(0.0, 0.0, 0.0);
     }
 
     match world.hit(r, 0.001, rt::INFINITY) {
-        Some(rec) => match rec.mat_ptr.scatter(r, &rec) {
-            Some((attenuation, scattered)) => attenuation * ray_color(&scattered, world, depth - 1),
-            None => rt::Color::new(0.0, 0.0, 0.0),
-        },
-        None => {
-            let unit_direction: rt::Vec3 = r.direction().unit_vector();
-            let t = 0.5 * (unit_direction.y() + 1.0);
-            (1.0 - t) * rt::Color::new(1.0, 1.0, 1.0) + t * rt::Color::new(0.5, 0.7, 1.0)
+        Some(rec) => {
+            let emitted = rec.mat_ptr.emitted(r, &rec);
+            match rec.mat_ptr.scatter(r, &rec) {
+                Some((attenuation, scattered)) => {
+                    let (depth, walk_steps) = next_depth(r, &rec, depth, walk_steps);
+                    emitted + attenuation * ray_color(&scattered, world, depth, walk_steps)
+                }
+                None => emitted,
+            }
         }
+        None => background(r),
     }
 }
 
-fn random_scene() -> rt::HittableList {
+/// Traces a path like `ray_color`, also recording what the camera ray hit first and splitting
+/// the light gathered into direct and indirect.
+pub fn ray_aovs(
+    r: &rt::Ray,
+    world: &rt::BvhNode,
+    depth: u64,
+    walk_steps: u64,
+    material_ids: &rt::MaterialIds,
+) -> rt::AovSample {
+    if depth == 0 {
+        return rt::AovSample::default();
+    }
+
+    let Some((object, rec)) = world.hit_object(r, 0.001, rt::INFINITY) else {
+        return rt::AovSample {
+            direct: background(r),
+            ..Default::default()
+        };
+    };
+
+    let mut sample = rt::AovSample {
+        depth: (rec.p - *r.origin()).length(),
+        normal: rec.normal,
+        object_id: Some(object),
+        material_id: material_ids.id(&rec.mat_ptr),
+        uv: (rec.u, rec.v),
+        position: rec.p,
+        direct: rec.mat_ptr.emitted(r, &rec),
+        ..Default::default()
+    };
+
+    // Light found where the first bounce lands is direct, anything gathered beyond it indirect
+    if let Some((attenuation, scattered)) = rec.mat_ptr.scatter(r, &rec) {
+        sample.albedo = attenuation;
+        let (depth, walk_steps) = next_depth(r, &rec, depth, walk_steps);
+        // Past the bounce limit `ray_color` gathers nothing, not even the background
+        if depth == 0 {
+            return sample;
+        }
+        match world.hit(&scattered, 0.001, rt::INFINITY) {
+            Some(next) => {
+                sample.direct += attenuation * next.mat_ptr.emitted(&scattered, &next);
+                if let Some((next_attenuation, next_scattered)) =
+                    next.mat_ptr.scatter(&scattered, &next)
+                {
+                    let (depth, walk_steps) = next_depth(&scattered, &next, depth, walk_steps);
+                    sample.indirect = attenuation
+                        * next_attenuation
+                        * ray_color(&next_scattered, world, depth, walk_steps);
+                }
+            }
+            None => sample.direct += attenuation * background(&scattered),
+        }
+    }
+    sample
+}
+
+fn background(r: &rt::Ray) -> rt::Color {
+    let unit_direction: rt::Vec3 = r.direction().unit_vector();
+    let t = 0.5 * (unit_direction.y() + 1.0);
+    (1.0 - t) * rt::Color::new(1.0, 1.0, 1.0) + t * rt::Color::new(0.5, 0.7, 1.0)
+}
+
+pub fn ray_color_spectral(
+    r: &rt::Ray,
+    world: &rt::BvhNode,
+    depth: u64,
+    walk_steps: u64,
+    lambdas: &mut rt::SampledWavelengths,
+) -> rt::SampledSpectrum {
+    // If we've exceeded the ray bounce limit, no more light is gathered.
+    if depth == 0 {
+        return rt::SampledSpectrum::constant(0.0);
+    }
+
+    match world.hit(r, 0.001, rt::INFINITY) {
+        Some(rec) => {
+            let emitted = rec.mat_ptr.emitted_spectral(r, &rec, lambdas);
+            match rec.mat_ptr.scatter_spectral(r, &rec, lambdas) {
+                Some((attenuation, scattered)) => {
+                    let (depth, walk_steps) = next_depth(r, &rec, depth, walk_steps);
+                    emitted
+                        + attenuation
+                            * ray_color_spectral(&scattered, world, depth, walk_steps, lambdas)
+                }
+                None => emitted,
+            }
+        }
+        None => rt::SampledSpectrum::from_rgb(&background(r), lambdas),
+    }
+}
+
+// A constant texture for one of the principled material's parameters
+fn value(x: f64) -> rt::TexturePtr {
+    Arc::new(rt::SolidColor::from_value(x))
+}
+
+/// Builds the scene, numbering its materials in `material_ids` as they are made.
+fn random_scene(material_ids: &mut rt::MaterialIds) -> rt::HittableList {
     let mut world = rt::HittableList::new();
-    let ground_material = Arc::new(rt::Lambertian::new(rt::Color::new(0.5, 0.5, 0.5)));
+    let ground_material = Arc::new(
+        rt::Principled::from_color(rt::Color::new(0.5, 0.5, 0.5)).with_roughness(value(1.0)),
+    );
+    material_ids.add(&ground_material);
     world.add(Box::new(rt::Sphere::new(
         rt::Point::new(0.0, -1000.0, 0.0),
         1000.0,
@@ -49,7 +166,8 @@ fn random_scene() -> rt::HittableList {
                 if choose_mat < 0.8 {
                     // diffuse
                     let albedo = rt::Color::random(0.0, 1.0) * rt::Color::random(0.0, 1.0);
-                    sphere_material = Arc::new(rt::Lambertian::new(albedo));
+                    sphere_material =
+                        Arc::new(rt::Principled::from_color(albedo).with_roughness(value(1.0)));
                     world.add(Box::new(rt::Sphere::new(
                         center,
                         0.2,
@@ -59,7 +177,11 @@ fn random_scene() -> rt::HittableList {
                     // metal
                     let albedo = rt::Color::random(0.5, 1.0);
                     let fuzz = rt::random_double(0.0, 0.5);
-                    sphere_material = Arc::new(rt::Metal::new(albedo, fuzz));
+                    sphere_material = Arc::new(
+                        rt::Principled::from_color(albedo)
+                            .with_metallic(value(1.0))
+                            .with_roughness(value(fuzz)),
+                    );
                     world.add(Box::new(rt::Sphere::new(
                         center,
                         0.2,
@@ -67,32 +189,42 @@ fn random_scene() -> rt::HittableList {
                     )));
                 } else {
                     // glass
-                    sphere_material = Arc::new(rt::Dielectric::new(1.5));
+                    sphere_material = Arc::new(glass());
                     world.add(Box::new(rt::Sphere::new(
                         center,
                         0.2,
                         Arc::clone(&sphere_material) as rt::MaterialPtr,
                     )));
                 }
+                material_ids.add(&sphere_material);
             }
         }
     }
 
-    let material1 = Arc::new(rt::Dielectric::new(1.5));
+    let material1 = Arc::new(glass());
+    material_ids.add(&material1);
     world.add(Box::new(rt::Sphere::new(
         rt::Point::new(0.0, 1.0, 0.0),
         1.0,
         Arc::clone(&material1) as rt::MaterialPtr,
     )));
 
-    let material2 = Arc::new(rt::Lambertian::new(rt::Color::new(0.4, 0.2, 0.1)));
+    let material2 = Arc::new(
+        rt::Principled::from_color(rt::Color::new(0.4, 0.2, 0.1)).with_roughness(value(1.0)),
+    );
+    material_ids.add(&material2);
     world.add(Box::new(rt::Sphere::new(
         rt::Point::new(-4.0, 1.0, 0.0),
         1.0,
         Arc::clone(&material2) as rt::MaterialPtr,
     )));
 
-    let material3 = Arc::new(rt::Metal::new(rt::Color::new(0.7, 0.6, 0.5), 0.0));
+    let material3 = Arc::new(
+        rt::Principled::from_color(rt::Color::new(0.7, 0.6, 0.5))
+            .with_metallic(value(1.0))
+            .with_roughness(value(0.0)),
+    );
+    material_ids.add(&material3);
     world.add(Box::new(rt::Sphere::new(
         rt::Point::new(4.0, 1.0, 0.0),
         1.0,
@@ -102,11 +234,65 @@ fn random_scene() -> rt::HittableList {
     world
 }
 
+// Clear glass with an index of refraction of 1.5
+fn glass() -> rt::Principled {
+    rt::Principled::from_color(rt::Color::new(1.0, 1.0, 1.0))
+        .with_roughness(value(0.0))
+        .with_transmission(value(1.0))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Projection {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+    CubeMap,
+    Stereo,
+    Ods,
+    Realistic,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
 #[derive(Parser, Debug)]
 #[command(author = "Abhijeet Krishnan <abhijeet.krishnan@gmail.com>", version = "0.1.0", about, long_about = None)]
 struct Args {
     #[arg(long, default_value_t = 4)]
     num_threads: usize,
+
+    /// Trace a few wavelengths per path instead of RGB
+    #[arg(long)]
+    spectral: bool,
+
+    /// Camera projection
+    #[arg(long, value_enum, default_value_t = Projection::Perspective)]
+    camera: Projection,
+
+    /// Focus the perspective camera on whatever is at the center of the image
+    #[arg(long)]
+    autofocus: bool,
+
+    /// Pixel reconstruction filter
+    #[arg(long, value_enum, default_value_t = FilterKind::Box)]
+    filter: FilterKind,
+
+    /// Filter radius in pixels, defaulting to one suited to the filter
+    #[arg(long)]
+    filter_radius: Option<f64>,
+
+    /// Also write depth, normal, albedo, ID, UV, position and direct/indirect light images as
+    /// PFM files into this directory. The light is traced in RGB, so this cannot be combined with
+    /// --spectral.
+    #[arg(long, conflicts_with = "spectral")]
+    aov_dir: Option<PathBuf>,
 }
 
 fn main() {
@@ -123,17 +309,31 @@ fn main() {
 
     let counter: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
 
-    // Buffer
-    let pixel_buffer = Arc::new(Mutex::new(vec![
-        vec![
-            rt::Color::new(0.0, 0.0, 0.0);
-            IMAGE_WIDTH
-        ];
-        IMAGE_HEIGHT
-    ]));
+    // Film
+    let filter: rt::FilterPtr = match args.filter {
+        FilterKind::Box => Arc::new(rt::BoxFilter::new(args.filter_radius.unwrap_or(0.5))),
+        FilterKind::Tent => Arc::new(rt::TentFilter::new(args.filter_radius.unwrap_or(1.0))),
+        FilterKind::Gaussian => Arc::new(rt::GaussianFilter::new(
+            args.filter_radius.unwrap_or(1.5),
+            0.5,
+        )),
+        FilterKind::Mitchell => Arc::new(rt::MitchellFilter::new(
+            args.filter_radius.unwrap_or(2.0),
+            1.0 / 3.0,
+            1.0 / 3.0,
+        )),
+        FilterKind::Lanczos => Arc::new(rt::LanczosFilter::new(args.filter_radius.unwrap_or(3.0))),
+    };
+    let film = Arc::new(Mutex::new(rt::Film::new(IMAGE_WIDTH, IMAGE_HEIGHT, filter)));
+    let aov_film = args
+        .aov_dir
+        .as_ref()
+        .map(|_| Arc::new(Mutex::new(rt::AovFilm::new(IMAGE_WIDTH, IMAGE_HEIGHT))));
 
     // World
-    let world = Arc::new(random_scene());
+    let mut material_ids = rt::MaterialIds::new();
+    let world = Arc::new(rt::BvhNode::new(random_scene(&mut material_ids)));
+    let material_ids = Arc::new(material_ids);
 
     // Camera
     let lookfrom = rt::Point::new(13.0, 2.0, 3.0);
@@ -141,15 +341,71 @@ fn main() {
     let vup = rt::Vec3::new(0.0, 1.0, 0.0);
     let dist_to_focus = 10.0;
     let aperture = 0.1;
-    let cam = Arc::new(rt::Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        rt::Degrees(20.0),
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-    ));
+    let cam: rt::CameraPtr = match args.camera {
+        Projection::Perspective => {
+            let camera = rt::PerspectiveCamera::new(
+                lookfrom,
+                lookat,
+                vup,
+                rt::Degrees(20.0),
+                ASPECT_RATIO,
+                aperture,
+                dist_to_focus,
+                0.0,
+                1.0,
+            );
+            if args.autofocus {
+                Arc::new(camera.autofocus(world.as_ref(), 0.5, 0.5))
+            } else {
+                Arc::new(camera)
+            }
+        }
+        Projection::Orthographic => Arc::new(rt::OrthographicCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            4.0,
+            ASPECT_RATIO,
+            0.0,
+            1.0,
+        )),
+        Projection::Fisheye => Arc::new(rt::FisheyeCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            rt::Degrees(180.0),
+            ASPECT_RATIO,
+            0.0,
+            1.0,
+        )),
+        Projection::Equirectangular => Arc::new(rt::EquirectangularCamera::new(
+            lookfrom, lookat, vup, 0.0, 1.0,
+        )),
+        Projection::CubeMap => Arc::new(rt::CubeMapCamera::new(lookfrom, lookat, vup, 0.0, 1.0)),
+        Projection::Stereo => Arc::new(rt::StereoCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            rt::Degrees(20.0),
+            ASPECT_RATIO / 2.0,
+            0.2,
+            dist_to_focus,
+            0.0,
+            1.0,
+        )),
+        Projection::Ods => Arc::new(rt::OdsCamera::new(lookfrom, lookat, vup, 0.2, 0.0, 1.0)),
+        Projection::Realistic => Arc::new(rt::RealisticCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            rt::LensElement::double_gauss_50mm(),
+            43.3,
+            ASPECT_RATIO,
+            dist_to_focus,
+            0.0,
+            1.0,
+        )),
+    };
 
     // Render
     print!("P3\n{IMAGE_WIDTH} {IMAGE_HEIGHT}\n255\n");
@@ -158,19 +414,52 @@ fn main() {
         for i in 0..IMAGE_WIDTH {
             // start a thread
             let ctr_t = Arc::clone(&counter);
-            let pixel_buffer_t = Arc::clone(&pixel_buffer);
+            let film_t = Arc::clone(&film);
+            let aov_film_t = aov_film.clone();
+            let material_ids_t = Arc::clone(&material_ids);
             let camera_t = Arc::clone(&cam);
             let world_t = Arc::clone(&world);
+            let spectral = args.spectral;
             pool.execute(move || {
                 for _ in 0..SAMPLES_PER_PIXEL {
-                    let u = (i as f64 + rt::random_double(0.0, 1.0)) / (IMAGE_WIDTH - 1) as f64;
-                    let v = (j as f64 + rt::random_double(0.0, 1.0)) / (IMAGE_HEIGHT - 1) as f64;
+                    let x = i as f64 + rt::random_double(0.0, 1.0);
+                    let y = j as f64 + rt::random_double(0.0, 1.0);
+                    let u = x / (IMAGE_WIDTH - 1) as f64;
+                    let v = y / (IMAGE_HEIGHT - 1) as f64;
                     let r = camera_t.get_ray(u, v);
-                    let ray_color = ray_color(&r, &world_t, MAX_DEPTH);
+                    let aovs = match (&aov_film_t, &r) {
+                        (Some(_), Some(r)) => Some(ray_aovs(
+                            r,
+                            &world_t,
+                            MAX_DEPTH,
+                            MAX_WALK_STEPS,
+                            &material_ids_t,
+                        )),
+                        (Some(_), None) => Some(rt::AovSample::default()),
+                        (None, _) => None,
+                    };
+                    let ray_color = match (r, &aovs) {
+                        (Some(r), _) if spectral => {
+                            let mut lambdas = rt::SampledWavelengths::random();
+                            ray_color_spectral(
+                                &r,
+                                &world_t,
+                                MAX_DEPTH,
+                                MAX_WALK_STEPS,
+                                &mut lambdas,
+                            )
+                            .to_rgb(&lambdas)
+                        }
+                        (Some(_), Some(aovs)) => aovs.direct + aovs.indirect,
+                        (Some(r), None) => ray_color(&r, &world_t, MAX_DEPTH, MAX_WALK_STEPS),
+                        (None, _) => rt::Color::new(0.0, 0.0, 0.0),
+                    };
 
-                    // acquire lock on curr pixel colour and update it
-                    let mut pixel_buffer = pixel_buffer_t.lock().unwrap();
-                    pixel_buffer[j][i] += ray_color;
+                    if let (Some(aov_film), Some(aovs)) = (&aov_film_t, &aovs) {
+                        aov_film.lock().unwrap().add_sample(i, j, aovs);
+                    }
+                    // acquire lock on the film and splat the sample onto it
+                    film_t.lock().unwrap().add_sample(x, y, ray_color);
                 }
                 let mut counter = ctr_t.lock().unwrap();
                 *counter += 1;
@@ -190,6 +479,20 @@ fn main() {
 
     pool.join();
 
-    rt::draw_buffer_to_ppm(pixel_buffer.lock().unwrap().to_vec(), SAMPLES_PER_PIXEL);
+    rt::draw_buffer_to_ppm(film.lock().unwrap().to_buffer(), 1);
+
+    if let (Some(dir), Some(aov_film)) = (&args.aov_dir, &aov_film) {
+        let aov_film = aov_film.lock().unwrap();
+        let written = fs::create_dir_all(dir).and_then(|_| {
+            rt::Aov::ALL.iter().try_for_each(|aov| {
+                aov_film
+                    .image(*aov)
+                    .save_pfm(dir.join(format!("{}.pfm", aov.name())))
+            })
+        });
+        if let Err(e) = written {
+            eprintln!("\nCould not write AOVs to {}: {e}", dir.display());
+        }
+    }
     eprintln!("\nDone");
 }
@@ -1,8 +1,48 @@
-use crate::{random_double, Color, HitRecord, Ray, Vec3};
-use num;
+use crate::spectrum::CHANNEL_WAVELENGTHS;
+use crate::{random_double, Color, HitRecord, Ray, SampledSpectrum, SampledWavelengths, Vec3};
 
 pub trait Material {
     fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+
+    /// Scatters a path carrying the given wavelengths in spectral mode. Materials that only
+    /// describe themselves in RGB have their attenuation upsampled to a spectrum.
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let (attenuation, scattered) = self.scatter(ray_in, rec)?;
+        Some((SampledSpectrum::from_rgb(&attenuation, lambdas), scattered))
+    }
+
+    /// Light given off at the hit, such as by a glowing volume.
+    fn emitted(&self, _ray_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// The spectral counterpart of `emitted`, which by default upsamples its RGB color.
+    fn emitted_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        SampledSpectrum::from_rgb(&self.emitted(ray_in, rec), lambdas)
+    }
+
+    /// Whether the ray ignores the surface at this hit and carries on, as through the cut-outs
+    /// of an alpha mask. The answer must be the same every time for the same ray and hit.
+    fn passes_through(&self, _ray_in: &Ray, _rec: &HitRecord) -> bool {
+        false
+    }
+
+    /// Whether scattering at this hit is a step of a random walk through the material's
+    /// interior, as under the surface of skin, rather than a bounce off a surface. Integrators
+    /// give walk steps a budget of their own.
+    fn walks_inside(&self, _ray_in: &Ray, _rec: &HitRecord) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
@@ -16,14 +56,14 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
         let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
 
         // Catch degenerate scatter direction
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal;
         }
-        let scattered = Ray::new(rec.p, scatter_direction);
+        let scattered = Ray::new(rec.p, scatter_direction, ray_in.time());
         let attenuation = self.albedo;
         Some((attenuation, scattered))
     }
@@ -43,7 +83,11 @@ impl Metal {
 impl Material for Metal {
     fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
         let reflected = Vec3::reflect(&ray_in.direction().unit_vector(), &rec.normal);
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
+        let scattered = Ray::new(
+            rec.p,
+            reflected + self.fuzz * Vec3::random_in_unit_sphere(),
+            ray_in.time(),
+        );
         let attenuation = self.albedo;
         if Vec3::dot(scattered.direction(), &rec.normal) > 0.0 {
             Some((attenuation, scattered))
@@ -53,44 +97,173 @@ impl Material for Metal {
     }
 }
 
+/// How a dielectric's index of refraction varies with wavelength, which splits white light into
+/// its colors.
+#[derive(Debug, Copy, Clone)]
+pub enum Dispersion {
+    /// `n = a + b / λ²`, with λ in micrometres.
+    Cauchy { a: f64, b: f64 },
+    /// `n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)`, with λ in micrometres.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Schott BK7 crown glass.
+    pub fn bk7() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    /// Fused silica.
+    pub fn fused_silica() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.00467914826, 0.0135120631, 97.9340025],
+        }
+    }
+
+    /// Schott SF11 dense flint glass, which disperses strongly.
+    pub fn sf11() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.73759695, 0.313747346, 1.89878101],
+            c: [0.013188707, 0.0623068142, 155.23629],
+        }
+    }
+
+    /// The index of refraction at a wavelength given in nanometres.
+    pub fn ior(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>()).sqrt()
+            }
+        }
+    }
+}
+
 pub struct Dielectric {
     ir: f64,
+    absorption: Color,
+    dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(ir: f64) -> Dielectric {
-        Dielectric { ir }
+        Dielectric {
+            ir,
+            absorption: Color::new(0.0, 0.0, 0.0),
+            dispersion: None,
+        }
     }
 
-    fn reflectance(&self, cosine: f64) -> f64 {
+    /// Absorbs light traveling through the interior following Beer-Lambert's law, with
+    /// `absorption` giving each channel's absorption coefficient per unit distance. A ray that
+    /// hits a back face is assumed to have started inside.
+    pub fn with_absorption(mut self, absorption: Color) -> Dielectric {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Varies the index of refraction with wavelength in place of the constant `ir`. Each
+    /// interaction refracts a single randomly chosen color channel.
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Dielectric {
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    fn reflectance(cosine: f64, ir: f64) -> f64 {
         // Use Schlick's approximation for reflectance
-        let r0 = (1.0 - self.ir) / (1.0 + self.ir);
+        let r0 = (1.0 - ir) / (1.0 + ir);
         let r0_squared = r0 * r0;
         r0_squared + (1.0 - r0_squared) * num::pow(1.0 - cosine, 5)
     }
 }
 
-impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+impl Dielectric {
+    // Reflects or refracts the incoming ray through an interface with the given index of
+    // refraction
+    fn scatter_with_ir(ray_in: &Ray, rec: &HitRecord, ir: f64) -> Ray {
+        let refraction_ratio = if rec.front_face { 1.0 / ir } else { ir };
 
         let unit_direction = ray_in.direction().unit_vector();
         let cos_theta = Vec3::dot(&-unit_direction, &rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract || self.reflectance(cos_theta) > random_double(0.0, 1.0) {
-            Vec3::reflect(&unit_direction, &rec.normal)
+        let direction =
+            if cannot_refract || Dielectric::reflectance(cos_theta, ir) > random_double(0.0, 1.0) {
+                Vec3::reflect(&unit_direction, &rec.normal)
+            } else {
+                Vec3::refract(&unit_direction, &rec.normal, refraction_ratio)
+            };
+
+        Ray::new(rec.p, direction, ray_in.time())
+    }
+
+    // The distance the incoming ray has traveled through the interior, if any
+    fn interior_distance(ray_in: &Ray, rec: &HitRecord) -> Option<f64> {
+        if rec.front_face {
+            None
         } else {
-            Vec3::refract(&unit_direction, &rec.normal, refraction_ratio)
+            Some(rec.t * ray_in.direction().length())
+        }
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let mut attenuation = Color::new(1.0, 1.0, 1.0);
+        if let Some(distance) = Dielectric::interior_distance(ray_in, rec) {
+            for i in 0..3 {
+                attenuation[i] = (-self.absorption[i] * distance).exp();
+            }
+        }
+
+        // Following one channel at a time and scaling it up by the number of channels keeps the
+        // average color unchanged
+        let ir = match self.dispersion {
+            Some(dispersion) => {
+                let channel = (random_double(0.0, 3.0) as u8).min(2);
+                for i in 0..3 {
+                    attenuation[i] = if i == channel {
+                        3.0 * attenuation[i]
+                    } else {
+                        0.0
+                    };
+                }
+                dispersion.ior(CHANNEL_WAVELENGTHS[channel as usize])
+            }
+            None => self.ir,
         };
 
-        let scattered = Ray::new(rec.p, direction);
-        Some((attenuation, scattered))
+        Some((attenuation, Dielectric::scatter_with_ir(ray_in, rec, ir)))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let attenuation = match Dielectric::interior_distance(ray_in, rec) {
+            Some(distance) => SampledSpectrum::from_rgb(&self.absorption, lambdas)
+                .map(|absorption| (-absorption * distance).exp()),
+            None => SampledSpectrum::constant(1.0),
+        };
+
+        // Each wavelength would refract in a different direction, so only the hero wavelength
+        // carries on
+        let ir = match self.dispersion {
+            Some(dispersion) => {
+                lambdas.terminate_secondary();
+                dispersion.ior(lambdas.hero())
+            }
+            None => self.ir,
+        };
+
+        Some((attenuation, Dielectric::scatter_with_ir(ray_in, rec, ir)))
     }
 }
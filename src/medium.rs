@@ -0,0 +1,465 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    blackbody, blackbody_rgb, random_double, Aabb, Color, HitRecord, Hittable, HittablePtr,
+    Isotropic, Material, MaterialPtr, Perlin, PhasePtr, Point, Ray, SampledSpectrum,
+    SampledWavelengths, Vec3, INFINITY,
+};
+
+// Hits inside a medium have no surface, so their normal and tangent are arbitrary
+fn medium_record(p: Point, t: f64, mat_ptr: MaterialPtr) -> HitRecord {
+    HitRecord {
+        p,
+        normal: Vec3::new(1.0, 0.0, 0.0),
+        mat_ptr,
+        t,
+        u: 0.0,
+        v: 0.0,
+        dpdu: Vec3::new(0.0, 1.0, 0.0),
+        front_face: true,
+    }
+}
+
+/// A volume of uniform density filling a convex boundary, such as fog or smoke. Light scatters
+/// isotropically unless given another phase function.
+pub struct ConstantMedium {
+    boundary: HittablePtr,
+    neg_inv_density: f64,
+    interaction: Arc<MediumInteraction>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: HittablePtr, density: f64, albedo: Color) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            interaction: Arc::new(MediumInteraction {
+                albedo,
+                phase: Arc::new(Isotropic),
+            }),
+        }
+    }
+
+    pub fn with_phase(mut self, phase: PhasePtr) -> ConstantMedium {
+        self.interaction = Arc::new(MediumInteraction {
+            albedo: self.interaction.albedo,
+            phase,
+        });
+        self
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let rec1 = self.boundary.hit(r, -INFINITY, INFINITY)?;
+        let rec2 = self.boundary.hit(r, rec1.t + 0.0001, INFINITY)?;
+
+        let t_enter = rec1.t.max(t_min).max(0.0);
+        let t_exit = rec2.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double(0.0, 1.0).ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        Some(medium_record(
+            r.at(t),
+            t,
+            Arc::clone(&self.interaction) as MaterialPtr,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// A grid of samples filling the unit cube, looked up with trilinear interpolation. Samples sit
+/// at the centers of the grid's cells and are stored with x varying fastest, then y, then z.
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f64>,
+}
+
+impl VoxelGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f64>) -> VoxelGrid {
+        assert!(
+            nx > 0 && ny > 0 && nz > 0,
+            "Voxel grids need at least one sample along each axis"
+        );
+        assert_eq!(
+            data.len(),
+            nx * ny * nz,
+            "Voxel data does not match its dimensions"
+        );
+        VoxelGrid { nx, ny, nz, data }
+    }
+
+    /// Samples `f` at the center of every cell, with coordinates in [0,1].
+    pub fn from_fn(nx: usize, ny: usize, nz: usize, f: impl Fn(Point) -> f64) -> VoxelGrid {
+        let mut data = Vec::with_capacity(nx * ny * nz);
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    data.push(f(Point::new(
+                        (i as f64 + 0.5) / nx as f64,
+                        (j as f64 + 0.5) / ny as f64,
+                        (k as f64 + 0.5) / nz as f64,
+                    )));
+                }
+            }
+        }
+        VoxelGrid::new(nx, ny, nz, data)
+    }
+
+    /// A puffy cloud of turbulent noise in [0,1] that fades out towards the edges of the cube.
+    /// Higher frequencies give smaller details.
+    pub fn cloud(n: usize, frequency: f64) -> VoxelGrid {
+        let perlin = Perlin::new();
+        VoxelGrid::from_fn(n, n, n, |p| {
+            let centered = p - Point::new(0.5, 0.5, 0.5);
+            let falloff = (1.0 - 2.0 * centered.length()).max(0.0);
+            (falloff * (0.5 + perlin.turb(&(frequency * p), 5))).min(1.0)
+        })
+    }
+
+    /// Loads a headerless grid of little-endian 32-bit floats.
+    pub fn load_raw(
+        path: impl AsRef<Path>,
+        nx: usize,
+        ny: usize,
+        nz: usize,
+    ) -> io::Result<VoxelGrid> {
+        if nx == 0 || ny == 0 || nz == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("A {nx}x{ny}x{nz} voxel grid has no samples"),
+            ));
+        }
+
+        let mut bytes = vec![];
+        BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+        if bytes.len() != nx * ny * nz * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Expected {} bytes for a {nx}x{ny}x{nz} voxel grid, found {}",
+                    nx * ny * nz * 4,
+                    bytes.len()
+                ),
+            ));
+        }
+
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        Ok(VoxelGrid::new(nx, ny, nz, data))
+    }
+
+    pub fn max_value(&self) -> f64 {
+        self.data.iter().cloned().fold(0.0, f64::max)
+    }
+
+    fn at(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.data[(k * self.ny + j) * self.nx + i]
+    }
+
+    /// The interpolated value at `p` in the unit cube. Points outside the cube take the value
+    /// of the nearest face.
+    pub fn lookup(&self, p: &Point) -> f64 {
+        // Position and weight along one axis between the two nearest samples
+        let axis = |x: f64, n: usize| {
+            let x = (x * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (x as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), x - i as f64)
+        };
+        let (i0, i1, tx) = axis(p.x(), self.nx);
+        let (j0, j1, ty) = axis(p.y(), self.ny);
+        let (k0, k1, tz) = axis(p.z(), self.nz);
+
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let plane = |k: usize| {
+            lerp(
+                lerp(self.at(i0, j0, k), self.at(i1, j0, k), tx),
+                lerp(self.at(i0, j1, k), self.at(i1, j1, k), tx),
+                ty,
+            )
+        };
+        lerp(plane(k0), plane(k1), tz)
+    }
+}
+
+// Light given off where a medium absorbs, from a color and a black-body temperature
+#[derive(Copy, Clone)]
+struct Emission {
+    color: Color,
+    temperature: f64,
+    temperature_scale: f64,
+}
+
+impl Emission {
+    // Only the fraction of collisions that are absorbed rather than scattered emits, which makes
+    // the emission proportional to the absorption coefficient
+    fn rgb(&self, albedo: &Color) -> Color {
+        let absorbed = Color::new(1.0, 1.0, 1.0) - *albedo;
+        absorbed * (self.color + self.temperature_scale * blackbody_rgb(self.temperature))
+    }
+
+    fn spectral(&self, albedo: &Color, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        let absorbed = SampledSpectrum::from_rgb(albedo, lambdas).map(|a| 1.0 - a);
+        let blackbody = SampledSpectrum::from_fn(lambdas, |lambda| {
+            self.temperature_scale * blackbody(lambda, self.temperature)
+        });
+        absorbed * (SampledSpectrum::from_rgb(&self.color, lambdas) + blackbody)
+    }
+}
+
+// The phase function is sampled exactly, so only the albedo weights the scattered light
+fn scatter_phase(phase: &PhasePtr, albedo: &Color, ray_in: &Ray, rec: &HitRecord) -> (Color, Ray) {
+    let direction = phase.sample(&ray_in.direction().unit_vector());
+    (*albedo, Ray::new(rec.p, direction, ray_in.time()))
+}
+
+// A point inside a uniform medium where light is scattered
+struct MediumInteraction {
+    albedo: Color,
+    phase: PhasePtr,
+}
+
+impl Material for MediumInteraction {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        Some(scatter_phase(&self.phase, &self.albedo, ray_in, rec))
+    }
+}
+
+// Maps a point in `bounds` to the unit cube
+fn to_local(bounds: &Aabb, p: &Point) -> Point {
+    let (min, max) = (bounds.min(), bounds.max());
+    let extent = max - min;
+    Point::new(
+        (p.x() - min.x()) / extent.x(),
+        (p.y() - min.y()) / extent.y(),
+        (p.z() - min.z()) / extent.z(),
+    )
+}
+
+// What happens at collisions inside a heterogeneous medium. It is shared by every collision and
+// looks its emission up from the grids at the hit point.
+#[derive(Clone)]
+struct GridInteraction {
+    bounds: Aabb,
+    albedo: Color,
+    phase: PhasePtr,
+    emission: Option<(Arc<VoxelGrid>, Color)>,
+    temperature: Option<(Arc<VoxelGrid>, f64, f64)>,
+}
+
+impl GridInteraction {
+    fn emission(&self, p: &Point) -> Emission {
+        let local = to_local(&self.bounds, p);
+        let color = match &self.emission {
+            Some((grid, color)) => grid.lookup(&local) * *color,
+            None => Color::new(0.0, 0.0, 0.0),
+        };
+        let (temperature, temperature_scale) = match &self.temperature {
+            Some((grid, kelvin_scale, scale)) => {
+                // The peak of Planck's law grows with the fifth power of the temperature
+                let relative = grid.lookup(&local).max(0.0);
+                (relative * kelvin_scale, scale * relative.powi(5))
+            }
+            None => (0.0, 0.0),
+        };
+        Emission {
+            color,
+            temperature,
+            temperature_scale,
+        }
+    }
+}
+
+impl Material for GridInteraction {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        Some(scatter_phase(&self.phase, &self.albedo, ray_in, rec))
+    }
+
+    fn emitted(&self, _ray_in: &Ray, rec: &HitRecord) -> Color {
+        if self.emission.is_none() && self.temperature.is_none() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.emission(&rec.p).rgb(&self.albedo)
+    }
+
+    fn emitted_spectral(
+        &self,
+        _ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        if self.emission.is_none() && self.temperature.is_none() {
+            return SampledSpectrum::constant(0.0);
+        }
+        self.emission(&rec.p).spectral(&self.albedo, lambdas)
+    }
+}
+
+/// A volume whose density varies through space, such as a cloud or smoke from a simulation. The
+/// density grid fills the box `bounds`.
+///
+/// Rays are tracked against a majorant, the grid's maximum density: they take exponentially
+/// distributed steps as if the volume were uniformly that dense, and at each step collide for
+/// real with a probability of the ratio of the local density to the majorant (delta tracking).
+pub struct HeterogeneousMedium {
+    bounds: Aabb,
+    density: VoxelGrid,
+    density_scale: f64,
+    majorant: f64,
+    interaction: Arc<GridInteraction>,
+}
+
+impl HeterogeneousMedium {
+    /// Fills `bounds` with `density`, with its values scaled by `density_scale` giving the
+    /// extinction coefficient per unit distance. The bounds must be finite, since the grid is
+    /// stretched across them.
+    pub fn new(
+        density: VoxelGrid,
+        bounds: Aabb,
+        density_scale: f64,
+        albedo: Color,
+    ) -> HeterogeneousMedium {
+        let extent = bounds.max() - bounds.min();
+        assert!(
+            (0..3).all(|axis| extent[axis] > 0.0 && extent[axis].is_finite()),
+            "Heterogeneous media need finite bounds with a volume"
+        );
+        HeterogeneousMedium {
+            bounds,
+            majorant: density_scale * density.max_value(),
+            density,
+            density_scale,
+            interaction: Arc::new(GridInteraction {
+                bounds,
+                albedo,
+                phase: Arc::new(Isotropic),
+                emission: None,
+                temperature: None,
+            }),
+        }
+    }
+
+    pub fn with_phase(mut self, phase: PhasePtr) -> HeterogeneousMedium {
+        Arc::make_mut(&mut self.interaction).phase = phase;
+        self
+    }
+
+    /// Emits `color` scaled by the grid's values wherever light would be absorbed.
+    pub fn with_emission(mut self, grid: VoxelGrid, color: Color) -> HeterogeneousMedium {
+        Arc::make_mut(&mut self.interaction).emission = Some((Arc::new(grid), color));
+        self
+    }
+
+    /// Emits black-body radiation for fire, at a temperature in kelvin of the grid's values
+    /// scaled by `kelvin_scale`. Where the grid is one, the brightest color component is
+    /// `intensity`, and cooler regions are dimmer and redder following Planck's law.
+    pub fn with_temperature(
+        mut self,
+        grid: VoxelGrid,
+        kelvin_scale: f64,
+        intensity: f64,
+    ) -> HeterogeneousMedium {
+        let rgb = blackbody_rgb(kelvin_scale);
+        let peak = rgb.x().max(rgb.y()).max(rgb.z());
+        let scale = if peak > 0.0 { intensity / peak } else { 0.0 };
+        Arc::make_mut(&mut self.interaction).temperature =
+            Some((Arc::new(grid), kelvin_scale, scale));
+        self
+    }
+
+    fn density_at(&self, p: &Point) -> f64 {
+        self.density_scale * self.density.lookup(&to_local(&self.bounds, p))
+    }
+
+    // The t of the next tentative collision after `t`
+    fn step(&self, t: f64, ray_length: f64) -> f64 {
+        t - (1.0 - random_double(0.0, 1.0)).ln() / (self.majorant * ray_length)
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let (t_enter, t_exit) = self.bounds.hit_interval(r, t_min, t_max)?;
+
+        let ray_length = r.direction().length();
+        let mut t = self.step(t_enter, ray_length);
+        while t < t_exit {
+            let p = r.at(t);
+            if random_double(0.0, 1.0) * self.majorant < self.density_at(&p) {
+                return Some(medium_record(
+                    p,
+                    t,
+                    Arc::clone(&self.interaction) as MaterialPtr,
+                ));
+            }
+            t = self.step(t, ray_length);
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_interpolates_between_cell_centers() {
+        let grid = VoxelGrid::new(2, 1, 1, vec![0.0, 1.0]);
+        assert_eq!(grid.lookup(&Point::new(0.25, 0.5, 0.5)), 0.0);
+        assert!((grid.lookup(&Point::new(0.5, 0.5, 0.5)) - 0.5).abs() < 1e-12);
+        assert_eq!(grid.lookup(&Point::new(2.0, -1.0, 0.5)), 1.0);
+
+        let single = VoxelGrid::new(1, 1, 1, vec![0.7]);
+        assert_eq!(single.lookup(&Point::new(0.9, 0.1, 0.4)), 0.7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_grids_are_rejected() {
+        VoxelGrid::new(0, 4, 4, vec![]);
+    }
+
+    #[test]
+    fn empty_raw_grids_are_rejected() {
+        let error = VoxelGrid::load_raw("missing.raw", 4, 0, 4).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unbounded_media_are_rejected() {
+        let bounds = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, INFINITY, 1.0));
+        HeterogeneousMedium::new(
+            VoxelGrid::cloud(4, 1.0),
+            bounds,
+            1.0,
+            Color::new(1.0, 1.0, 1.0),
+        );
+    }
+}
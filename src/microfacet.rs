@@ -0,0 +1,339 @@
+use crate::spectrum::interpolate_channels;
+use crate::{
+    random_double, Color, HitRecord, Material, Onb, Ray, SampledSpectrum, SampledWavelengths, Vec3,
+    PI,
+};
+
+/// Fresnel reflectance of a dielectric interface, for light arriving at `cos_theta_i` to the
+/// normal. `eta` is the ratio of the index of refraction on the far side to the near side.
+pub(crate) fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    0.5 * (r_parl * r_parl + r_perp * r_perp)
+}
+
+/// Fresnel reflectance of a conductor with complex index of refraction `eta + i*k`, for light
+/// arriving from a medium with an index of refraction of one.
+pub(crate) fn fresnel_conductor(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+    0.5 * (r_p + r_s)
+}
+
+/// The Trowbridge-Reitz (GGX) microfacet distribution with Smith height-correlated
+/// masking-shadowing. Directions are in the local shading frame, with the normal along +z.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    /// Perceptual roughness in [0,1] is squared to get the distribution's width, which gives a
+    /// more even progression from mirror-like to matte.
+    pub(crate) fn from_roughness(roughness: f64) -> Ggx {
+        Ggx {
+            alpha: (roughness * roughness).max(1e-4),
+        }
+    }
+
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * (-1.0 + (1.0 + self.alpha * self.alpha * tan2).sqrt())
+    }
+
+    pub(crate) fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    pub(crate) fn g2(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal from the distribution of normals visible from `wo`, which must
+    /// lie in the upper hemisphere (Heitz 2018).
+    pub(crate) fn sample_visible_normal(&self, wo: &Vec3) -> Vec3 {
+        // Stretch the view direction to the configuration of a hemisphere
+        let vh = Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).unit_vector();
+
+        let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if lensq > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&vh, &t1);
+
+        // Sample the projected area of the hemisphere
+        let r = random_double(0.0, 1.0).sqrt();
+        let phi = 2.0 * PI * random_double(0.0, 1.0);
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // Unstretch back to the ellipsoid configuration
+        Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)).unit_vector()
+    }
+}
+
+/// A rough metal described by its complex index of refraction, with a separate `eta` and
+/// absorption coefficient `k` for each color channel.
+pub struct RoughConductor {
+    eta: Color,
+    k: Color,
+    distribution: Ggx,
+}
+
+impl RoughConductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> RoughConductor {
+        RoughConductor {
+            eta,
+            k,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> RoughConductor {
+        RoughConductor::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub(crate) fn fresnel(&self, cos_theta_i: f64) -> Color {
+        Color::new(
+            fresnel_conductor(cos_theta_i, self.eta.x(), self.k.x()),
+            fresnel_conductor(cos_theta_i, self.eta.y(), self.k.y()),
+            fresnel_conductor(cos_theta_i, self.eta.z(), self.k.z()),
+        )
+    }
+}
+
+impl RoughConductor {
+    // Samples a reflected ray, returning it with the cosine between the outgoing direction and
+    // the microfacet normal and the shadowing weight
+    fn sample(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, f64, f64)> {
+        let uvw = Onb::build_from_w(&rec.normal);
+        let wo = uvw.to_local(&-ray_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let wm = self.distribution.sample_visible_normal(&wo);
+        let wi = Vec3::reflect(&-wo, &wm);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // Sampling visible normals leaves only the Fresnel term and the shadowing of the
+        // outgoing direction in the weight
+        let weight = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+        let scattered = Ray::new(rec.p, uvw.local(&wi), ray_in.time());
+        Some((scattered, Vec3::dot(&wo, &wm), weight))
+    }
+}
+
+impl Material for RoughConductor {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let (scattered, cos_theta_m, weight) = self.sample(ray_in, rec)?;
+        Some((self.fresnel(cos_theta_m) * weight, scattered))
+    }
+
+    // Evaluating the Fresnel equations per wavelength, rather than upsampling the RGB
+    // reflectance, keeps the hue shifts of metals at grazing angles
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let (scattered, cos_theta_m, weight) = self.sample(ray_in, rec)?;
+        let fresnel = SampledSpectrum::from_fn(lambdas, |lambda| {
+            let eta = interpolate_channels(&self.eta, lambda);
+            let k = interpolate_channels(&self.k, lambda);
+            fresnel_conductor(cos_theta_m, eta, k)
+        });
+        Some((fresnel * weight, scattered))
+    }
+}
+
+/// Rough glass: light is reflected or refracted through microfacets drawn from a GGX
+/// distribution.
+pub struct RoughDielectric {
+    ir: f64,
+    distribution: Ggx,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, roughness: f64) -> RoughDielectric {
+        RoughDielectric {
+            ir,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let uvw = Onb::build_from_w(&rec.normal);
+        let wo = uvw.to_local(&-ray_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        // The normal always faces the incoming ray, so the far side is inside when we hit the
+        // front face
+        let eta = if rec.front_face {
+            self.ir
+        } else {
+            1.0 / self.ir
+        };
+
+        let wm = self.distribution.sample_visible_normal(&wo);
+        let cos_theta_m = Vec3::dot(&wo, &wm);
+        let reflectance = fresnel_dielectric(cos_theta_m, eta);
+
+        let wi = if random_double(0.0, 1.0) < reflectance {
+            let wi = Vec3::reflect(&-wo, &wm);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi = Vec3::refract(&-wo, &wm, 1.0 / eta);
+            if wi.z() >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        let attenuation = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+        let scattered = Ray::new(rec.p, uvw.local(&wi), ray_in.time());
+        Some((Color::new(attenuation, attenuation, attenuation), scattered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_material, Hittable, Point, Sphere};
+
+    fn random_upper_direction() -> Vec3 {
+        let w = Vec3::random_unit_vector();
+        Vec3::new(w.x(), w.y(), w.z().abs().max(1e-3)).unit_vector()
+    }
+
+    #[test]
+    fn visible_normals_face_the_viewer() {
+        for roughness in [0.0, 0.1, 0.5, 1.0] {
+            let distribution = Ggx::from_roughness(roughness);
+            for _ in 0..1000 {
+                let wo = random_upper_direction();
+                let wm = distribution.sample_visible_normal(&wo);
+                assert!((wm.length() - 1.0).abs() < 1e-9);
+                assert!(wm.z() > 0.0, "{wm:?}");
+                assert!(Vec3::dot(&wo, &wm) >= -1e-9, "{wo:?} {wm:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn masking_is_a_fraction() {
+        let distribution = Ggx::from_roughness(0.7);
+        assert!((distribution.g1(&Vec3::new(0.0, 0.0, 1.0)) - 1.0).abs() < 1e-12);
+        for _ in 0..100 {
+            let (wo, wi) = (random_upper_direction(), random_upper_direction());
+            let g2 = distribution.g2(&wo, &wi);
+            assert!(g2 > 0.0 && g2 <= distribution.g1(&wo).min(distribution.g1(&wi)));
+        }
+    }
+
+    #[test]
+    fn fresnel_reflectance() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert_eq!(fresnel_dielectric(0.1, 1.0 / 1.5), 1.0);
+        assert!((fresnel_conductor(0.0, 0.2, 3.0) - 1.0).abs() < 1e-9);
+
+        let presets = [
+            RoughConductor::gold(0.2),
+            RoughConductor::copper(0.2),
+            RoughConductor::aluminium(0.2),
+            RoughConductor::silver(0.2),
+        ];
+        for conductor in presets {
+            for i in 0..=100 {
+                let f = conductor.fresnel(i as f64 / 100.0);
+                for c in 0..3u8 {
+                    assert!((0.0..=1.0).contains(&f[c]), "{f:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rough_conductors_reflect_above_the_surface() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, test_material());
+        let conductor = RoughConductor::gold(0.6);
+        for _ in 0..1000 {
+            let origin = 3.0 * Vec3::random_unit_vector();
+            let r = Ray::new(origin, Vec3::random_in_unit_sphere() - origin, 0.0);
+            let Some(rec) = sphere.hit(&r, 0.001, f64::INFINITY) else {
+                continue;
+            };
+            if let Some((attenuation, scattered)) = conductor.scatter(&r, &rec) {
+                assert!(Vec3::dot(scattered.direction(), &rec.normal) > 0.0);
+                for c in 0..3u8 {
+                    assert!((0.0..=1.0).contains(&attenuation[c]));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use crate::alphamask::hash_hit;
+use crate::{
+    Color, HitRecord, Material, MaterialPtr, Ray, SampledSpectrum, SampledWavelengths, SolidColor,
+    TexturePtr,
+};
+
+/// Blends two materials by randomly choosing one of them at each hit, with the first channel of
+/// `weight` giving the chance of choosing `b`. A noise or image weight gives effects such as rust
+/// patches over metal.
+pub struct MixMaterial {
+    a: MaterialPtr,
+    b: MaterialPtr,
+    weight: TexturePtr,
+}
+
+impl MixMaterial {
+    pub fn new(a: MaterialPtr, b: MaterialPtr, weight: TexturePtr) -> MixMaterial {
+        MixMaterial { a, b, weight }
+    }
+
+    pub fn from_weight(a: MaterialPtr, b: MaterialPtr, weight: f64) -> MixMaterial {
+        MixMaterial::new(a, b, Arc::new(SolidColor::from_value(weight)))
+    }
+
+    // The choice comes from the ray and hit rather than a fresh random number, so that deciding
+    // whether a ray passes through and then scattering it agree on the material
+    fn choose(&self, ray_in: &Ray, rec: &HitRecord) -> &MaterialPtr {
+        if hash_hit(ray_in, rec, 1) < self.weight.value(rec.u, rec.v, &rec.p).x() {
+            &self.b
+        } else {
+            &self.a
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.choose(ray_in, rec).scatter(ray_in, rec)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        self.choose(ray_in, rec)
+            .scatter_spectral(ray_in, rec, lambdas)
+    }
+
+    fn passes_through(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.choose(ray_in, rec).passes_through(ray_in, rec)
+    }
+
+    fn walks_inside(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.choose(ray_in, rec).walks_inside(ray_in, rec)
+    }
+}
+
+/// Gives the front and back faces of a surface different materials, such as the two sides of a
+/// leaf or a painted sheet.
+pub struct TwoSided {
+    front: MaterialPtr,
+    back: MaterialPtr,
+}
+
+impl TwoSided {
+    pub fn new(front: MaterialPtr, back: MaterialPtr) -> TwoSided {
+        TwoSided { front, back }
+    }
+
+    fn side(&self, rec: &HitRecord) -> &MaterialPtr {
+        if rec.front_face {
+            &self.front
+        } else {
+            &self.back
+        }
+    }
+}
+
+impl Material for TwoSided {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.side(rec).scatter(ray_in, rec)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        self.side(rec).scatter_spectral(ray_in, rec, lambdas)
+    }
+
+    fn passes_through(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.side(rec).passes_through(ray_in, rec)
+    }
+
+    fn walks_inside(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.side(rec).walks_inside(ray_in, rec)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    Color, HitRecord, Material, MaterialPtr, Onb, Ray, SampledSpectrum, SampledWavelengths,
+    TexturePtr, Vec3,
+};
+
+// Replaces the shading normal with `local`, given in tangent space: x along the surface's dpdu,
+// y along the bitangent and z along the outward normal
+fn perturb(rec: &HitRecord, local: &Vec3) -> HitRecord {
+    let outward = if rec.front_face {
+        rec.normal
+    } else {
+        -rec.normal
+    };
+
+    // Surfaces without a usable tangent (such as the poles of a sphere) fall back to an arbitrary
+    // one
+    let tangent = rec.dpdu - Vec3::dot(&rec.dpdu, &outward) * outward;
+    let tangent = if tangent.near_zero() {
+        Onb::build_from_w(&outward).u()
+    } else {
+        tangent.unit_vector()
+    };
+    let bitangent = Vec3::cross(&outward, &tangent);
+
+    // A normal tilted below the surface would have materials scatter light into it, so it is
+    // kept just above
+    let local = if local.near_zero() {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(local.x(), local.y(), local.z().max(0.01 * local.length()))
+    };
+    let normal = (local.x() * tangent + local.y() * bitangent + local.z() * outward).unit_vector();
+    let mut rec = rec.clone();
+    rec.normal = if rec.front_face { normal } else { -normal };
+    rec
+}
+
+/// Perturbs the shading normal of a material with a tangent space normal map, where red, green
+/// and blue encode the x, y and z components in [0,1].
+pub struct NormalMapped {
+    base: MaterialPtr,
+    normal_map: TexturePtr,
+}
+
+impl NormalMapped {
+    pub fn new(base: MaterialPtr, normal_map: TexturePtr) -> NormalMapped {
+        NormalMapped { base, normal_map }
+    }
+
+    fn shade(&self, rec: &HitRecord) -> HitRecord {
+        let encoded = self.normal_map.value(rec.u, rec.v, &rec.p);
+        let local = 2.0 * encoded - Vec3::new(1.0, 1.0, 1.0);
+        perturb(rec, &local)
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.base.scatter(ray_in, &self.shade(rec))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        self.base
+            .scatter_spectral(ray_in, &self.shade(rec), lambdas)
+    }
+
+    fn passes_through(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.base.passes_through(ray_in, rec)
+    }
+
+    fn walks_inside(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.base.walks_inside(ray_in, rec)
+    }
+}
+
+/// Perturbs the shading normal of a material as if its surface were displaced by the first
+/// channel of a height texture. `scale` converts the texture's values to heights across one unit
+/// of texture coordinates, so larger values give deeper bumps.
+pub struct BumpMapped {
+    base: MaterialPtr,
+    height: TexturePtr,
+    scale: f64,
+}
+
+impl BumpMapped {
+    // Step in texture coordinates for the finite differences
+    const DELTA: f64 = 1.0 / 1024.0;
+
+    pub fn new(base: MaterialPtr, height: TexturePtr, scale: f64) -> BumpMapped {
+        BumpMapped {
+            base,
+            height,
+            scale,
+        }
+    }
+
+    fn shade(&self, rec: &HitRecord) -> HitRecord {
+        let height = |u: f64, v: f64| self.scale * self.height.value(u, v, &rec.p).x();
+        let h = height(rec.u, rec.v);
+        let dhdu = (height(rec.u + BumpMapped::DELTA, rec.v) - h) / BumpMapped::DELTA;
+        let dhdv = (height(rec.u, rec.v + BumpMapped::DELTA) - h) / BumpMapped::DELTA;
+        perturb(rec, &Vec3::new(-dhdu, -dhdv, 1.0))
+    }
+}
+
+impl Material for BumpMapped {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.base.scatter(ray_in, &self.shade(rec))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        self.base
+            .scatter_spectral(ray_in, &self.shade(rec), lambdas)
+    }
+
+    fn passes_through(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.base.passes_through(ray_in, rec)
+    }
+
+    fn walks_inside(&self, ray_in: &Ray, rec: &HitRecord) -> bool {
+        self.base.walks_inside(ray_in, rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_material, Hittable, Point, Sphere};
+
+    #[test]
+    fn perturbed_normals_stay_above_the_surface() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, test_material());
+        for origin in [Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, 0.5)] {
+            let r = Ray::new(origin, Vec3::new(0.05, 0.1, -1.0), 0.0);
+            let rec = sphere.hit(&r, 0.001, f64::INFINITY).unwrap();
+            for local in [
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(-0.3, 2.0, -1.0),
+                Vec3::new(0.0, 0.0, 0.0),
+            ] {
+                let perturbed = perturb(&rec, &local);
+                assert!((perturbed.normal.length() - 1.0).abs() < 1e-9);
+                assert!(Vec3::dot(&perturbed.normal, &rec.normal) > 0.0);
+            }
+        }
+    }
+}
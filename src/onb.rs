@@ -0,0 +1,48 @@
+use crate::Vec3;
+
+/// An orthonormal basis, with `w` typically aligned to a surface normal.
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: &Vec3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::cross(&w, &a).unit_vector();
+        let u = Vec3::cross(&v, &w);
+        Onb { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Transforms a vector expressed in this basis into world space.
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    /// Transforms a world space vector into this basis.
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(
+            Vec3::dot(a, &self.u),
+            Vec3::dot(a, &self.v),
+            Vec3::dot(a, &self.w),
+        )
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use crate::{random_double, Onb, Vec3, PI};
+
+/// How a medium redistributes the light it scatters, as a function of the angle between the
+/// directions of travel before and after scattering.
+pub trait PhaseFunction {
+    /// The probability density of scattering through an angle with cosine `cos_theta`, which
+    /// integrates to one over the sphere of directions.
+    fn p(&self, cos_theta: f64) -> f64;
+
+    /// Draws the cosine of a scattering angle with density `p`.
+    fn sample_cos_theta(&self) -> f64;
+
+    /// A new direction of travel for light scattered while traveling along `direction`.
+    fn sample(&self, direction: &Vec3) -> Vec3 {
+        let cos_theta = self.sample_cos_theta().clamp(-1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * random_double(0.0, 1.0);
+        Onb::build_from_w(direction).local(&Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+pub type PhasePtr = Arc<dyn PhaseFunction + Send + Sync>;
+
+/// Scatters equally in all directions, which suits fog and smoke.
+pub struct Isotropic;
+
+impl PhaseFunction for Isotropic {
+    fn p(&self, _cos_theta: f64) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        1.0 - 2.0 * random_double(0.0, 1.0)
+    }
+}
+
+/// The Henyey-Greenstein phase function. The asymmetry `g` in (-1,1) is the average cosine of the
+/// scattering angle: positive values scatter forwards as in clouds and skin, negative values
+/// backwards, and zero is isotropic.
+pub struct HenyeyGreenstein {
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein {
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn p(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        let g = self.g;
+        let u = random_double(0.0, 1.0);
+        if g.abs() < 1e-3 {
+            return 1.0 - 2.0 * u;
+        }
+        let sq = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+        (1.0 + g * g - sq * sq) / (2.0 * g)
+    }
+}
+
+/// A blend of two Henyey-Greenstein lobes, usually a strong forward lobe with a weaker backward
+/// one, which matches clouds better than a single lobe. `weight` is the share of the first lobe.
+pub struct DoubleHenyeyGreenstein {
+    first: HenyeyGreenstein,
+    second: HenyeyGreenstein,
+    weight: f64,
+}
+
+impl DoubleHenyeyGreenstein {
+    pub fn new(g1: f64, g2: f64, weight: f64) -> DoubleHenyeyGreenstein {
+        DoubleHenyeyGreenstein {
+            first: HenyeyGreenstein::new(g1),
+            second: HenyeyGreenstein::new(g2),
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl PhaseFunction for DoubleHenyeyGreenstein {
+    fn p(&self, cos_theta: f64) -> f64 {
+        self.weight * self.first.p(cos_theta) + (1.0 - self.weight) * self.second.p(cos_theta)
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        if random_double(0.0, 1.0) < self.weight {
+            self.first.sample_cos_theta()
+        } else {
+            self.second.sample_cos_theta()
+        }
+    }
+}
+
+/// Scattering by particles much smaller than the wavelength of light, such as the molecules of
+/// air that make the sky blue.
+pub struct Rayleigh;
+
+impl PhaseFunction for Rayleigh {
+    fn p(&self, cos_theta: f64) -> f64 {
+        3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta)
+    }
+
+    // Inverts the CDF, which leaves the cubic mu^3 + 3mu + 4 - 8u = 0 with a single real root
+    fn sample_cos_theta(&self) -> f64 {
+        let q = 4.0 - 8.0 * random_double(0.0, 1.0);
+        let a = (-0.5 * q + (0.25 * q * q + 1.0).sqrt()).cbrt();
+        a - 1.0 / a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase_functions() -> Vec<PhasePtr> {
+        vec![
+            Arc::new(Isotropic),
+            Arc::new(HenyeyGreenstein::new(0.7)),
+            Arc::new(HenyeyGreenstein::new(-0.4)),
+            Arc::new(HenyeyGreenstein::new(0.0005)),
+            Arc::new(DoubleHenyeyGreenstein::new(0.8, -0.3, 0.7)),
+            Arc::new(Rayleigh),
+        ]
+    }
+
+    // The probability of a cosine in [a,b], integrating p over the band of the sphere
+    fn band_probability(phase: &PhasePtr, a: f64, b: f64) -> f64 {
+        let steps = 2000;
+        let h = (b - a) / steps as f64;
+        (0..steps)
+            .map(|i| 2.0 * PI * phase.p(a + (i as f64 + 0.5) * h) * h)
+            .sum()
+    }
+
+    #[test]
+    fn densities_integrate_to_one() {
+        for phase in phase_functions() {
+            let total = band_probability(&phase, -1.0, 1.0);
+            assert!((total - 1.0).abs() < 1e-3, "{total}");
+        }
+    }
+
+    #[test]
+    fn sampling_follows_the_density() {
+        let (samples, bins) = (200_000, 10);
+        for phase in phase_functions() {
+            let mut histogram = vec![0; bins];
+            for _ in 0..samples {
+                let cos_theta = phase.sample_cos_theta();
+                assert!((-1.0..=1.0).contains(&cos_theta), "{cos_theta}");
+                let bin = ((cos_theta + 1.0) / 2.0 * bins as f64) as usize;
+                histogram[bin.min(bins - 1)] += 1;
+            }
+            for (bin, &count) in histogram.iter().enumerate() {
+                let a = -1.0 + 2.0 * bin as f64 / bins as f64;
+                let expected = band_probability(&phase, a, a + 2.0 / bins as f64);
+                let found = count as f64 / samples as f64;
+                assert!((found - expected).abs() < 0.01, "{bin}: {found} {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn directions_are_measured_from_the_direction_of_travel() {
+        let direction = Vec3::new(1.0, -2.0, 0.5);
+        let phase = HenyeyGreenstein::new(0.6);
+        let samples = 100_000;
+        let mut mean_cos = 0.0;
+        for _ in 0..samples {
+            let scattered = phase.sample(&direction);
+            assert!((scattered.length() - 1.0).abs() < 1e-9);
+            mean_cos += Vec3::dot(&scattered, &direction.unit_vector()) / samples as f64;
+        }
+        assert!((mean_cos - 0.6).abs() < 0.01, "{mean_cos}");
+    }
+}
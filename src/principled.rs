@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use crate::microfacet::{fresnel_dielectric, Ggx};
+use crate::{
+    random_double, Color, HitRecord, Material, Onb, Ray, SolidColor, TexturePtr, Vec3, PI,
+};
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+fn constant(value: f64) -> TexturePtr {
+    Arc::new(SolidColor::from_value(value))
+}
+
+/// A Disney-style principled material, blending diffuse, metallic, specular, clearcoat, sheen
+/// and transmission behaviour from a small set of artist friendly parameters in [0,1]. Every
+/// parameter can be driven by a texture; single-valued parameters read the texture's first
+/// channel.
+///
+/// Each scatter picks one lobe at random according to the parameters, so the lobes' weights only
+/// have to account for the parts of the BSDF that are not importance sampled.
+pub struct Principled {
+    base_color: TexturePtr,
+    metallic: TexturePtr,
+    roughness: TexturePtr,
+    specular: TexturePtr,
+    clearcoat: TexturePtr,
+    sheen: TexturePtr,
+    transmission: TexturePtr,
+}
+
+impl Principled {
+    /// A rough dielectric of the given color. The remaining parameters can be set with the
+    /// `with_*` methods.
+    pub fn new(base_color: TexturePtr) -> Principled {
+        Principled {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            clearcoat: constant(0.0),
+            sheen: constant(0.0),
+            transmission: constant(0.0),
+        }
+    }
+
+    pub fn from_color(base_color: Color) -> Principled {
+        Principled::new(Arc::new(SolidColor::new(base_color)))
+    }
+
+    pub fn with_metallic(mut self, metallic: TexturePtr) -> Principled {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: TexturePtr) -> Principled {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Scales the reflectance at normal incidence of the dielectric base, where 0.5 corresponds
+    /// to an index of refraction of 1.5.
+    pub fn with_specular(mut self, specular: TexturePtr) -> Principled {
+        self.specular = specular;
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: TexturePtr) -> Principled {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: TexturePtr) -> Principled {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: TexturePtr) -> Principled {
+        self.transmission = transmission;
+        self
+    }
+
+    fn scalar(texture: &TexturePtr, rec: &HitRecord) -> f64 {
+        texture.value(rec.u, rec.v, &rec.p).x().clamp(0.0, 1.0)
+    }
+}
+
+// Reflects `wo` about a microfacet normal sampled from `distribution`, returning the incoming
+// direction together with the cosine between `wo` and the microfacet normal
+fn sample_reflection(distribution: &Ggx, wo: &Vec3) -> Option<(Vec3, f64, f64)> {
+    let wm = distribution.sample_visible_normal(wo);
+    let wi = Vec3::reflect(&-wo, &wm);
+    if wi.z() <= 0.0 {
+        return None;
+    }
+    let weight = distribution.g2(wo, &wi) / distribution.g1(wo);
+    Some((wi, Vec3::dot(wo, &wm), weight))
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let base_color = self.base_color.value(rec.u, rec.v, &rec.p);
+        let metallic = Principled::scalar(&self.metallic, rec);
+        let roughness = Principled::scalar(&self.roughness, rec);
+        let specular = Principled::scalar(&self.specular, rec);
+        let clearcoat = Principled::scalar(&self.clearcoat, rec);
+        let sheen = Principled::scalar(&self.sheen, rec);
+        let transmission = Principled::scalar(&self.transmission, rec);
+
+        let uvw = Onb::build_from_w(&rec.normal);
+        let wo = uvw.to_local(&-ray_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let distribution = Ggx::from_roughness(roughness);
+        let scattered = |wi: Vec3| Ray::new(rec.p, uvw.local(&wi), ray_in.time());
+
+        // Clearcoat: a thin, glossy, uncolored layer on top of everything else. Choosing it with
+        // the probability that it reflects leaves only the shadowing term in the weight.
+        if rec.front_face && random_double(0.0, 1.0) < clearcoat * fresnel_dielectric(wo.z(), 1.5) {
+            let (wi, _, weight) = sample_reflection(&Ggx::from_roughness(0.1), &wo)?;
+            return Some((Color::new(weight, weight, weight), scattered(wi)));
+        }
+
+        // Metal: colored specular reflection, with the base color as the reflectance at normal
+        // incidence
+        if random_double(0.0, 1.0) < metallic {
+            let (wi, cos_theta_m, weight) = sample_reflection(&distribution, &wo)?;
+            let white = Color::new(1.0, 1.0, 1.0);
+            let fresnel = base_color + schlick_weight(cos_theta_m) * (white - base_color);
+            return Some((weight * fresnel, scattered(wi)));
+        }
+
+        // Dielectric: the index of refraction follows from the reflectance at normal incidence
+        let f0 = (0.08 * specular).max(1e-4);
+        let ior = (1.0 + f0.sqrt()) / (1.0 - f0.sqrt());
+        let eta = if rec.front_face { ior } else { 1.0 / ior };
+
+        if random_double(0.0, 1.0) < transmission {
+            let wm = distribution.sample_visible_normal(&wo);
+            let cos_theta_m = Vec3::dot(&wo, &wm);
+            if random_double(0.0, 1.0) < fresnel_dielectric(cos_theta_m, eta) {
+                let wi = Vec3::reflect(&-wo, &wm);
+                if wi.z() <= 0.0 {
+                    return None;
+                }
+                let weight = distribution.g2(&wo, &wi) / distribution.g1(&wo);
+                return Some((Color::new(weight, weight, weight), scattered(wi)));
+            }
+            let wi = Vec3::refract(&-wo, &wm, 1.0 / eta);
+            if wi.z() >= 0.0 {
+                return None;
+            }
+            let weight = distribution.g2(&wo, &wi) / distribution.g1(&wo);
+            return Some((weight * base_color, scattered(wi)));
+        }
+
+        // Opaque dielectric: a specular highlight over a diffuse base
+        if random_double(0.0, 1.0) < fresnel_dielectric(wo.z(), eta) {
+            let (wi, _, weight) = sample_reflection(&distribution, &wo)?;
+            return Some((Color::new(weight, weight, weight), scattered(wi)));
+        }
+
+        // Cosine weighted diffuse, with the Disney retro-reflection term and a sheen that
+        // brightens grazing angles
+        let mut wi = Vec3::new(0.0, 0.0, 1.0) + Vec3::random_unit_vector();
+        if wi.near_zero() {
+            wi = Vec3::new(0.0, 0.0, 1.0);
+        }
+        let wi = wi.unit_vector();
+        let wh = (wi + wo).unit_vector();
+        let cos_theta_d = Vec3::dot(&wi, &wh);
+        let fd90 = 0.5 + 2.0 * roughness * cos_theta_d * cos_theta_d;
+        let retro = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z()))
+            * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+        // Cosine weighted sampling turns a lobe f into a throughput of π·f. The diffuse lobe's
+        // 1/π cancels, but the sheen lobe has none.
+        let sheen = sheen * schlick_weight(cos_theta_d) * PI;
+        let attenuation = retro * base_color + Color::new(sheen, sheen, sheen);
+        Some((attenuation, scattered(wi)))
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use crate::{Aabb, HitRecord, Hittable, HittableList, MaterialPtr, Onb, Point, Ray, Vec3, PI};
+
+/// A parallelogram with corner `q` and edges `u` and `v`.
+pub struct Quad {
+    q: Point,
+    u: Vec3,
+    v: Vec3,
+    mat_ptr: MaterialPtr,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+}
+
+impl Quad {
+    pub fn new(q: Point, u: Vec3, v: Vec3, m: MaterialPtr) -> Quad {
+        let n = Vec3::cross(&u, &v);
+        let normal = n.unit_vector();
+        let d = Vec3::dot(&normal, &q);
+        let w = n / Vec3::dot(&n, &n);
+
+        Quad {
+            q,
+            u,
+            v,
+            mat_ptr: m,
+            normal,
+            d,
+            w,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = Vec3::dot(&self.normal, r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - Vec3::dot(&self.normal, r.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // Express the hit point in terms of the edge vectors to find out if it lies within the
+        // parallelogram
+        let p = r.at(t);
+        let planar_hitpt_vector = p - self.q;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar_hitpt_vector, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar_hitpt_vector));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let (front_face, normal) = HitRecord::get_face_normal(r, self.normal);
+
+        Some(HitRecord {
+            p,
+            normal,
+            mat_ptr: Arc::clone(&self.mat_ptr),
+            t,
+            u: alpha,
+            v: beta,
+            dpdu: self.u,
+            front_face,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[
+            self.q,
+            self.q + self.u,
+            self.q + self.v,
+            self.q + self.u + self.v,
+        ]))
+    }
+}
+
+/// An infinite plane through `point`. Texture coordinates repeat every unit of distance.
+pub struct Plane {
+    point: Point,
+    basis: Onb,
+    mat_ptr: MaterialPtr,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, m: MaterialPtr) -> Plane {
+        Plane {
+            point,
+            basis: Onb::build_from_w(&normal),
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let normal = self.basis.w();
+        let denom = Vec3::dot(&normal, r.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = Vec3::dot(&normal, &(self.point - r.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = r.at(t);
+        let local = self.basis.to_local(&(p - self.point));
+        let (front_face, normal) = HitRecord::get_face_normal(r, normal);
+
+        Some(HitRecord {
+            p,
+            normal,
+            mat_ptr: Arc::clone(&self.mat_ptr),
+            t,
+            u: local.x().rem_euclid(1.0),
+            v: local.y().rem_euclid(1.0),
+            dpdu: self.basis.u(),
+            front_face,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// A flat circular disk. `u` is the angle around the normal and `v` the distance from the center,
+/// both mapped to [0,1].
+pub struct Disk {
+    center: Point,
+    radius: f64,
+    basis: Onb,
+    mat_ptr: MaterialPtr,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vec3, radius: f64, m: MaterialPtr) -> Disk {
+        Disk {
+            center,
+            radius,
+            basis: Onb::build_from_w(&normal),
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let normal = self.basis.w();
+        let denom = Vec3::dot(&normal, r.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = Vec3::dot(&normal, &(self.center - r.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = r.at(t);
+        let local = self.basis.to_local(&(p - self.center));
+        let dist = local.length();
+        if dist > self.radius {
+            return None;
+        }
+
+        let phi = local.y().atan2(local.x()) + PI;
+        let (front_face, normal) = HitRecord::get_face_normal(r, normal);
+
+        Some(HitRecord {
+            p,
+            normal,
+            mat_ptr: Arc::clone(&self.mat_ptr),
+            t,
+            u: phi / (2.0 * PI),
+            v: dist / self.radius,
+            dpdu: self.basis.local(&Vec3::new(-local.y(), local.x(), 0.0)),
+            front_face,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (u, v) = (self.radius * self.basis.u(), self.radius * self.basis.v());
+        Some(Aabb::from_points(&[
+            self.center + u + v,
+            self.center + u - v,
+            self.center - u + v,
+            self.center - u - v,
+        ]))
+    }
+}
+
+/// An axis-aligned box spanning two opposite corners, built out of six quads.
+pub struct Cuboid {
+    sides: HittableList,
+}
+
+impl Cuboid {
+    pub fn new(a: Point, b: Point, m: MaterialPtr) -> Cuboid {
+        let min = Point::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let max = Point::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+        let mut sides = HittableList::new();
+        let faces = [
+            (Point::new(min.x(), min.y(), max.z()), dx, dy), // front
+            (Point::new(max.x(), min.y(), max.z()), -dz, dy), // right
+            (Point::new(max.x(), min.y(), min.z()), -dx, dy), // back
+            (Point::new(min.x(), min.y(), min.z()), dz, dy), // left
+            (Point::new(min.x(), max.y(), max.z()), dx, -dz), // top
+            (Point::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+        ];
+        for (q, u, v) in faces {
+            sides.add(Box::new(Quad::new(q, u, v, Arc::clone(&m))));
+        }
+
+        Cuboid { sides }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.sides.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.sides.bounding_box()
+    }
+}
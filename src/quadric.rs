@@ -0,0 +1,726 @@
+use std::sync::Arc;
+
+use crate::{
+    degrees_to_radians, Aabb, Degrees, HitRecord, Hittable, MaterialPtr, Onb, Point, Ray, Vec3, PI,
+};
+
+// Relative tolerance of the polynomial solvers. Each test compares against the size of the
+// coefficients involved, so that scaling a polynomial does not change which branch it takes.
+const EPS: f64 = 1e-9;
+
+/// Real roots of `a*x^2 + b*x + c = 0` in increasing order.
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // The roots q / a and c / q below stay accurate however small a gets, as long as it is not 0
+    if a == 0.0 {
+        if b == 0.0 {
+            return vec![];
+        }
+        return vec![-c / b];
+    }
+
+    // Rounding can push the discriminant of a double root slightly negative
+    let discriminant = b * b - 4.0 * a * c;
+    let discriminant = if discriminant.abs() <= EPS * (b * b).max((4.0 * a * c).abs()) {
+        0.0
+    } else if discriminant < 0.0 {
+        return vec![];
+    } else {
+        discriminant
+    };
+
+    // Avoid cancellation between -b and the square root of the discriminant
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    if t0 < t1 {
+        vec![t0, t1]
+    } else {
+        vec![t1, t0]
+    }
+}
+
+/// Real roots of `x^3 + a*x^2 + b*x + c = 0`, in no particular order.
+fn solve_normalized_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // Substitute x = y - a/3 to eliminate the quadratic term: y^3 + 3p*y + 2q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    // The size of the roots, which sets the scale of q (a cube) and d (a sixth power)
+    let size = p.abs().sqrt().max(q.abs().cbrt());
+    let roots = if d.abs() <= EPS * size.powi(6) {
+        if q.abs() <= EPS * size.powi(3) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // Three real roots, use the trigonometric solution
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.0).cos(),
+            -t * (phi - PI / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    roots.into_iter().map(|y| y - a / 3.0).collect()
+}
+
+/// Real roots of `c4*x^4 + c3*x^3 + c2*x^2 + c1*x + c0 = 0` in increasing order.
+///
+/// Uses Ferrari's method followed by a few Newton iterations on the original polynomial, which
+/// recovers most of the precision lost in the closed form.
+pub(crate) fn solve_quartic(c4: f64, c3: f64, c2: f64, c1: f64, c0: f64) -> Vec<f64> {
+    // A vanishing leading coefficient would put a root out near c3 / c4, so compare them
+    if c4.abs() <= EPS * c3.abs() || c4 == 0.0 {
+        let mut roots = if c3.abs() <= EPS * c2.abs() || c3 == 0.0 {
+            solve_quadratic(c2, c1, c0)
+        } else {
+            solve_normalized_cubic(c2 / c3, c1 / c3, c0 / c3)
+        };
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        return roots;
+    }
+
+    let (a, b, c, d) = (c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+
+    // Substitute x = y - a/4 to eliminate the cubic term: y^4 + p*y^2 + q*y + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c / 4.0 + d;
+
+    // A bound on the size of the roots, which sets the scale of p (a square), q (a cube) and r (a
+    // fourth power), and of the rounding errors in them
+    let size = a
+        .abs()
+        .max(b.abs().sqrt())
+        .max(c.abs().cbrt())
+        .max(d.abs().sqrt().sqrt());
+    let sq_size = size * size;
+
+    let mut roots = if r.abs() <= EPS * sq_size * sq_size {
+        // No absolute term: y * (y^3 + p*y + q) = 0
+        let mut roots = solve_normalized_cubic(0.0, p, q);
+        roots.push(0.0);
+        roots
+    } else {
+        // Solve the resolvent cubic and use its largest root to factor the quartic into
+        // (y^2 + z)^2 = (v*y - u)^2. The largest keeps 2z - p and z^2 - r from going negative
+        // when the quartic has real roots.
+        let z = solve_normalized_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0)
+            .into_iter()
+            .fold(f64::MIN, f64::max);
+
+        let sq_v = 2.0 * z - p;
+        let sq_u = z * z - r;
+        let (u, v) = if sq_v > EPS * sq_size {
+            // Taking u from q avoids the cancellation in z^2 - r
+            let v = sq_v.sqrt();
+            (q / (2.0 * v), v)
+        } else if sq_v < -EPS * sq_size || sq_u < -EPS * sq_size * sq_size {
+            return vec![];
+        } else {
+            (sq_u.max(0.0).sqrt(), 0.0)
+        };
+
+        let mut roots = solve_quadratic(1.0, -v, z + u);
+        roots.extend(solve_quadratic(1.0, v, z - u));
+        roots
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 4.0;
+        for _ in 0..3 {
+            let x = *root;
+            let f = (((c4 * x + c3) * x + c2) * x + c1) * x + c0;
+            let df = ((4.0 * c4 * x + 3.0 * c3) * x + 2.0 * c2) * x + c1;
+            if df == 0.0 {
+                break;
+            }
+            // Near a double root the step can overshoot, so only keep improvements
+            let next = x - f / df;
+            let next_f = (((c4 * next + c3) * next + c2) * next + c1) * next + c0;
+            if next_f.abs() > f.abs() {
+                break;
+            }
+            *root = next;
+        }
+    }
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
+
+// Angle of a local space point around the z axis, in [0, 2pi)
+fn phi_of(p: &Point) -> f64 {
+    let phi = p.y().atan2(p.x());
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+// A candidate intersection, in the local space of a quadric
+struct LocalHit {
+    t: f64,
+    p: Point,
+    normal: Vec3,
+    u: f64,
+    v: f64,
+}
+
+impl LocalHit {
+    fn closer(self, other: Option<LocalHit>) -> LocalHit {
+        match other {
+            Some(other) if other.t < self.t => other,
+            _ => self,
+        }
+    }
+}
+
+// The quadrics are all defined around the local z axis. The frame maps local space to world space
+// by placing the local origin at `base` and aligning the local z axis with the world space `axis`.
+struct Frame {
+    base: Point,
+    basis: Onb,
+}
+
+impl Frame {
+    fn new(base: Point, axis: Vec3) -> Frame {
+        Frame {
+            base,
+            basis: Onb::build_from_w(&axis),
+        }
+    }
+
+    fn to_local(&self, r: &Ray) -> (Point, Vec3) {
+        (
+            self.basis.to_local(&(r.origin() - self.base)),
+            self.basis.to_local(r.direction()),
+        )
+    }
+
+    fn hit_record(&self, r: &Ray, hit: LocalHit, mat_ptr: &MaterialPtr) -> HitRecord {
+        let outward_normal = self.basis.local(&hit.normal).unit_vector();
+        let (front_face, normal) = HitRecord::get_face_normal(r, outward_normal);
+        HitRecord {
+            p: self.base + self.basis.local(&hit.p),
+            normal,
+            mat_ptr: Arc::clone(mat_ptr),
+            t: hit.t,
+            u: hit.u,
+            v: hit.v,
+            // Every quadric's u runs counterclockwise around the local z axis
+            dpdu: self.basis.local(&Vec3::new(-hit.p.y(), hit.p.x(), 0.0)),
+            front_face,
+        }
+    }
+
+    fn bounding_box(&self, min: Point, max: Point) -> Aabb {
+        let corners = Aabb::new(min, max)
+            .corners()
+            .map(|corner| self.base + self.basis.local(&corner));
+        Aabb::from_points(&corners)
+    }
+}
+
+// Intersects a (possibly partial) disk cap of the given radius in the plane z = `z`
+#[allow(clippy::too_many_arguments)]
+fn hit_cap(
+    o: &Point,
+    d: &Vec3,
+    t_min: f64,
+    t_max: f64,
+    z: f64,
+    radius: f64,
+    phi_max: f64,
+    normal_z: f64,
+) -> Option<LocalHit> {
+    let t = (z - o.z()) / d.z();
+    if !t.is_finite() || t < t_min || t > t_max {
+        return None;
+    }
+    let p = o + t * d;
+    let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+    let phi = phi_of(&p);
+    if rho > radius || phi > phi_max {
+        return None;
+    }
+    Some(LocalHit {
+        t,
+        p,
+        normal: Vec3::new(0.0, 0.0, normal_z),
+        u: phi / phi_max,
+        v: rho / radius,
+    })
+}
+
+/// A cylinder of the given radius extending `height` along `axis` from `base`, swept through
+/// `phi_max` degrees around the axis. Capped cylinders are closed off with disks at both ends.
+pub struct Cylinder {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    mat_ptr: MaterialPtr,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        phi_max: Degrees,
+        capped: bool,
+        m: MaterialPtr,
+    ) -> Cylinder {
+        Cylinder {
+            frame: Frame::new(base, axis),
+            radius,
+            height,
+            phi_max: degrees_to_radians(phi_max).0,
+            capped,
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = self.frame.to_local(r);
+        let mut closest: Option<LocalHit> = None;
+
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+        for t in solve_quadratic(a, b, c) {
+            if t < t_min || t > t_max {
+                continue;
+            }
+            let p = o + t * d;
+            let phi = phi_of(&p);
+            if p.z() < 0.0 || p.z() > self.height || phi > self.phi_max {
+                continue;
+            }
+            closest = Some(LocalHit {
+                t,
+                p,
+                normal: Vec3::new(p.x(), p.y(), 0.0) / self.radius,
+                u: phi / self.phi_max,
+                v: p.z() / self.height,
+            });
+            break;
+        }
+
+        if self.capped {
+            for (z, normal_z) in [(0.0, -1.0), (self.height, 1.0)] {
+                let t_max = closest.as_ref().map_or(t_max, |hit| hit.t);
+                let cap = hit_cap(&o, &d, t_min, t_max, z, self.radius, self.phi_max, normal_z);
+                if let Some(cap) = cap {
+                    closest = Some(cap.closer(closest));
+                }
+            }
+        }
+
+        closest.map(|hit| self.frame.hit_record(r, hit, &self.mat_ptr))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.frame.bounding_box(
+            Point::new(-self.radius, -self.radius, 0.0),
+            Point::new(self.radius, self.radius, self.height),
+        ))
+    }
+}
+
+/// A cone with its base of the given radius at `base` and its apex `height` along `axis`, swept
+/// through `phi_max` degrees around the axis. Capped cones are closed off with a disk at the base.
+pub struct Cone {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    mat_ptr: MaterialPtr,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        phi_max: Degrees,
+        capped: bool,
+        m: MaterialPtr,
+    ) -> Cone {
+        Cone {
+            frame: Frame::new(base, axis),
+            radius,
+            height,
+            phi_max: degrees_to_radians(phi_max).0,
+            capped,
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = self.frame.to_local(r);
+        let mut closest: Option<LocalHit> = None;
+
+        // x^2 + y^2 = (k * (h - z))^2
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h_oz = self.height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y() + k2 * h_oz * d.z());
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * h_oz * h_oz;
+        for t in solve_quadratic(a, b, c) {
+            if t < t_min || t > t_max {
+                continue;
+            }
+            let p = o + t * d;
+            let phi = phi_of(&p);
+            if p.z() < 0.0 || p.z() > self.height || phi > self.phi_max {
+                continue;
+            }
+            closest = Some(LocalHit {
+                t,
+                p,
+                normal: Vec3::new(p.x(), p.y(), k2 * (self.height - p.z())),
+                u: phi / self.phi_max,
+                v: p.z() / self.height,
+            });
+            break;
+        }
+
+        if self.capped {
+            let t_max = closest.as_ref().map_or(t_max, |hit| hit.t);
+            if let Some(cap) = hit_cap(&o, &d, t_min, t_max, 0.0, self.radius, self.phi_max, -1.0) {
+                closest = Some(cap.closer(closest));
+            }
+        }
+
+        closest.map(|hit| self.frame.hit_record(r, hit, &self.mat_ptr))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.frame.bounding_box(
+            Point::new(-self.radius, -self.radius, 0.0),
+            Point::new(self.radius, self.radius, self.height),
+        ))
+    }
+}
+
+/// A paraboloid with its vertex at `base`, opening along `axis` until it reaches the given radius
+/// at `height`, swept through `phi_max` degrees around the axis.
+pub struct Paraboloid {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    mat_ptr: MaterialPtr,
+}
+
+impl Paraboloid {
+    pub fn new(
+        base: Point,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        phi_max: Degrees,
+        m: MaterialPtr,
+    ) -> Paraboloid {
+        Paraboloid {
+            frame: Frame::new(base, axis),
+            radius,
+            height,
+            phi_max: degrees_to_radians(phi_max).0,
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = self.frame.to_local(r);
+
+        // x^2 + y^2 = k * z
+        let k = self.radius * self.radius / self.height;
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y()) - k * d.z();
+        let c = o.x() * o.x() + o.y() * o.y() - k * o.z();
+        for t in solve_quadratic(a, b, c) {
+            if t < t_min || t > t_max {
+                continue;
+            }
+            let p = o + t * d;
+            let phi = phi_of(&p);
+            if p.z() < 0.0 || p.z() > self.height || phi > self.phi_max {
+                continue;
+            }
+            let hit = LocalHit {
+                t,
+                p,
+                normal: Vec3::new(2.0 * p.x(), 2.0 * p.y(), -k),
+                u: phi / self.phi_max,
+                v: p.z() / self.height,
+            };
+            return Some(self.frame.hit_record(r, hit, &self.mat_ptr));
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.frame.bounding_box(
+            Point::new(-self.radius, -self.radius, 0.0),
+            Point::new(self.radius, self.radius, self.height),
+        ))
+    }
+}
+
+/// A torus centered at `center` whose ring lies in the plane perpendicular to `axis`, swept
+/// through `phi_max` degrees around the axis. `u` runs around the ring and `v` around the tube.
+pub struct Torus {
+    frame: Frame,
+    major_radius: f64,
+    minor_radius: f64,
+    phi_max: f64,
+    mat_ptr: MaterialPtr,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        phi_max: Degrees,
+        m: MaterialPtr,
+    ) -> Torus {
+        Torus {
+            frame: Frame::new(center, axis),
+            major_radius,
+            minor_radius,
+            phi_max: degrees_to_radians(phi_max).0,
+            mat_ptr: m,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = self.frame.to_local(r);
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+
+        // Work with a unit direction and move the origin close to the torus, which keeps the
+        // quartic coefficients well conditioned for distant rays
+        let d_len = d.length();
+        let dir = d / d_len;
+        let shift = (-Vec3::dot(&o, &dir) - (big_r + small_r)).max(0.0);
+        let o = o + shift * dir;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2)
+        let four_r2 = 4.0 * big_r * big_r;
+        let beta = 2.0 * Vec3::dot(&o, &dir);
+        let gamma = o.length_squared() + big_r * big_r - small_r * small_r;
+        let c4 = 1.0;
+        let c3 = 2.0 * beta;
+        let c2 = beta * beta + 2.0 * gamma - four_r2 * (dir.x() * dir.x() + dir.y() * dir.y());
+        let c1 = 2.0 * beta * gamma - 2.0 * four_r2 * (o.x() * dir.x() + o.y() * dir.y());
+        let c0 = gamma * gamma - four_r2 * (o.x() * o.x() + o.y() * o.y());
+
+        for s in solve_quartic(c4, c3, c2, c1, c0) {
+            let t = (s + shift) / d_len;
+            if t < t_min || t > t_max {
+                continue;
+            }
+            let p = o + s * dir;
+            let phi = phi_of(&p);
+            if phi > self.phi_max {
+                continue;
+            }
+
+            let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+            let theta = p.z().atan2(rho - big_r);
+            let theta = if theta < 0.0 { theta + 2.0 * PI } else { theta };
+            let s2 = p.length_squared() + big_r * big_r - small_r * small_r;
+            let normal = s2 * p - 2.0 * big_r * big_r * Vec3::new(p.x(), p.y(), 0.0);
+            let hit = LocalHit {
+                t,
+                p,
+                normal,
+                u: phi / self.phi_max,
+                v: theta / (2.0 * PI),
+            };
+            return Some(self.frame.hit_record(r, hit, &self.mat_ptr));
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = self.major_radius + self.minor_radius;
+        Some(self.frame.bounding_box(
+            Point::new(-extent, -extent, -self.minor_radius),
+            Point::new(extent, extent, self.minor_radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_material;
+
+    fn assert_roots(found: &[f64], expected: &[f64], tolerance: f64) {
+        assert_eq!(
+            found.len(),
+            expected.len(),
+            "roots {found:?}, expected {expected:?}"
+        );
+        for (f, e) in found.iter().zip(expected) {
+            assert!(
+                (f - e).abs() < tolerance,
+                "roots {found:?}, expected {expected:?}"
+            );
+        }
+    }
+
+    // Coefficients of the monic polynomial with the given roots, highest power first
+    fn from_roots(roots: &[f64]) -> Vec<f64> {
+        let mut coefficients = vec![1.0];
+        for root in roots {
+            let mut next = coefficients.clone();
+            next.push(0.0);
+            for (i, c) in coefficients.iter().enumerate() {
+                next[i + 1] -= root * c;
+            }
+            coefficients = next;
+        }
+        coefficients
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(&solve_quadratic(1.0, -4.0, 3.0), &[1.0, 3.0], 1e-12);
+        assert_roots(&solve_quadratic(2.0, 0.0, 2.0), &[], 0.0);
+        assert_roots(&solve_quadratic(0.0, 2.0, -1.0), &[0.5], 1e-12);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        let mut roots = solve_normalized_cubic(-6.0, 11.0, -6.0);
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_roots(&roots, &[1.0, 2.0, 3.0], 1e-9);
+
+        // (x - 1)^2 (x + 2), a double root
+        let mut roots = solve_normalized_cubic(0.0, -3.0, 2.0);
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_roots(&roots, &[-2.0, 1.0], 1e-9);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        let c = from_roots(&[1.0, 2.0, 3.0, 4.0]);
+        assert_roots(
+            &solve_quartic(c[0], c[1], c[2], c[3], c[4]),
+            &[1.0, 2.0, 3.0, 4.0],
+            1e-9,
+        );
+
+        // (x^2 + 1)(x - 1)(x - 2) has only two real roots
+        let c = from_roots(&[1.0, 2.0]);
+        let roots = solve_quartic(c[0], c[1], c[2] + c[0], c[1], c[2]);
+        assert_roots(&roots, &[1.0, 2.0], 1e-9);
+
+        // No real roots at all
+        assert_roots(&solve_quartic(1.0, 0.0, 2.0, 0.0, 5.0), &[], 0.0);
+    }
+
+    #[test]
+    fn quartic_double_roots() {
+        // (x^2 - 1)^2, where the resolvent cubic has a double root
+        let roots = solve_quartic(1.0, 0.0, -2.0, 0.0, 1.0);
+        assert!(!roots.is_empty());
+        for root in roots {
+            assert!((root.abs() - 1.0).abs() < 1e-6, "root {root}");
+        }
+    }
+
+    #[test]
+    fn quartic_roots_are_scale_invariant() {
+        for scale in [1e-6, 1.0, 1e6] {
+            let expected = [-3.0 * scale, -0.5 * scale, 0.25 * scale, 2.0 * scale];
+            let c = from_roots(&expected);
+            let roots = solve_quartic(c[0], c[1], c[2], c[3], c[4]);
+            assert_roots(&roots, &expected, 1e-7 * scale);
+        }
+    }
+
+    #[test]
+    fn quartic_roots_over_a_grid() {
+        let values = [-5.0, -2.5, -1.0, -0.1, 0.3, 1.0, 2.0, 7.5];
+        for &a in &values {
+            for &b in &values {
+                for &c in &values {
+                    for &d in &values {
+                        let mut expected = vec![a, b, c, d];
+                        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                        let co = from_roots(&expected);
+                        let roots = solve_quartic(co[0], co[1], co[2], co[3], co[4]);
+
+                        // Repeated roots may come back once or several times, and less precisely
+                        assert!(!roots.is_empty(), "no roots for {expected:?}");
+                        for root in &roots {
+                            assert!(
+                                expected.iter().any(|e| (root - e).abs() < 1e-4),
+                                "root {root} not in {expected:?}"
+                            );
+                        }
+                        for e in &expected {
+                            assert!(
+                                roots.iter().any(|root| (root - e).abs() < 1e-4),
+                                "missed {e} in {roots:?}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn torus_hit_along_its_plane() {
+        let material = test_material();
+        let torus = Torus::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            Degrees(360.0),
+            material,
+        );
+        let r = Ray::new(Point::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = torus.hit(&r, 0.001, crate::INFINITY).unwrap();
+        assert!((rec.p.x() + 2.5).abs() < 1e-6, "hit at {:?}", rec.p);
+
+        // Starting inside the tube, the nearest hit is the inner wall
+        let r = Ray::new(Point::new(-2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = torus.hit(&r, 0.001, crate::INFINITY).unwrap();
+        assert!((rec.p.x() + 1.5).abs() < 1e-6, "hit at {:?}", rec.p);
+    }
+}
@@ -3,13 +3,15 @@ use crate::{Point, Vec3};
 pub struct Ray {
     orig: Point,
     dir: Vec3,
+    tm: f64,
 }
 
 impl Ray {
-    pub fn new(origin: Point, direction: Vec3) -> Ray {
+    pub fn new(origin: Point, direction: Vec3, time: f64) -> Ray {
         Ray {
             orig: origin,
             dir: direction,
+            tm: time,
         }
     }
 
@@ -21,6 +23,10 @@ impl Ray {
         &self.dir
     }
 
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
+
     pub fn at(&self, t: f64) -> Point {
         self.orig + t * self.dir
     }
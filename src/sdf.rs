@@ -0,0 +1,472 @@
+use std::sync::Arc;
+
+use crate::{clamp, Aabb, HitRecord, Hittable, MaterialPtr, Point, Ray, Vec3, PI};
+
+/// A signed distance function: negative inside the surface, positive outside, and never larger
+/// than the true distance to the surface.
+pub trait SignedDistance {
+    fn distance(&self, p: &Point) -> f64;
+
+    /// The box enclosing the surface, or `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+pub type SdfPtr = Arc<dyn SignedDistance + Send + Sync>;
+
+fn max_components(v: &Vec3) -> Vec3 {
+    Vec3::new(v.x().max(0.0), v.y().max(0.0), v.z().max(0.0))
+}
+
+fn pad(bbox: Aabb, amount: f64) -> Aabb {
+    let offset = Vec3::new(amount, amount, amount);
+    Aabb::new(bbox.min() - offset, bbox.max() + offset)
+}
+
+/// A box with rounded edges, given its center, half extents and the radius of the rounding.
+pub struct SdfRoundedBox {
+    center: Point,
+    half_extents: Vec3,
+    radius: f64,
+}
+
+impl SdfRoundedBox {
+    pub fn new(center: Point, half_extents: Vec3, radius: f64) -> SdfRoundedBox {
+        SdfRoundedBox {
+            center,
+            half_extents,
+            radius,
+        }
+    }
+}
+
+impl SignedDistance for SdfRoundedBox {
+    fn distance(&self, p: &Point) -> f64 {
+        let p = p - self.center;
+        let r = self.radius;
+        let q = Vec3::new(p.x().abs(), p.y().abs(), p.z().abs()) - self.half_extents
+            + Vec3::new(r, r, r);
+        max_components(&q).length() + q.x().max(q.y().max(q.z())).min(0.0) - r
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.center - self.half_extents,
+            self.center + self.half_extents,
+        ))
+    }
+}
+
+/// A line segment from `a` to `b` swept by a sphere of the given radius.
+pub struct SdfCapsule {
+    a: Point,
+    b: Point,
+    radius: f64,
+}
+
+impl SdfCapsule {
+    pub fn new(a: Point, b: Point, radius: f64) -> SdfCapsule {
+        SdfCapsule { a, b, radius }
+    }
+}
+
+impl SignedDistance for SdfCapsule {
+    fn distance(&self, p: &Point) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = clamp(Vec3::dot(&pa, &ba) / ba.length_squared(), 0.0, 1.0);
+        (pa - h * ba).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = Aabb::new(self.a, self.b);
+        Some(pad(bbox, self.radius))
+    }
+}
+
+/// A torus lying in the XZ plane around `center`.
+pub struct SdfTorus {
+    center: Point,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl SdfTorus {
+    pub fn new(center: Point, major_radius: f64, minor_radius: f64) -> SdfTorus {
+        SdfTorus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl SignedDistance for SdfTorus {
+    fn distance(&self, p: &Point) -> f64 {
+        let p = p - self.center;
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major_radius;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = self.major_radius + self.minor_radius;
+        Some(Aabb::new(
+            self.center - Vec3::new(extent, self.minor_radius, extent),
+            self.center + Vec3::new(extent, self.minor_radius, extent),
+        ))
+    }
+}
+
+/// The Mandelbulb fractal, scaled so that it fits in a sphere of roughly `scale` around `center`.
+pub struct Mandelbulb {
+    center: Point,
+    scale: f64,
+    power: f64,
+    iterations: u32,
+}
+
+impl Mandelbulb {
+    pub fn new(center: Point, scale: f64, power: f64, iterations: u32) -> Mandelbulb {
+        Mandelbulb {
+            center,
+            scale,
+            power,
+            iterations,
+        }
+    }
+}
+
+impl SignedDistance for Mandelbulb {
+    fn distance(&self, p: &Point) -> f64 {
+        let c = (p - self.center) / self.scale;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = z.length();
+
+        for _ in 0..self.iterations {
+            if r > 2.0 || r == 0.0 {
+                break;
+            }
+
+            // Raise z to the given power in spherical coordinates and add the starting point
+            let theta = (z.z() / r).acos() * self.power;
+            let phi = z.y().atan2(z.x()) * self.power;
+            let zr = r.powf(self.power);
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            z =
+                zr * Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ) + c;
+            r = z.length();
+        }
+
+        if r == 0.0 {
+            return 0.0;
+        }
+        self.scale * 0.5 * r.ln() * r / dr
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = 1.2 * self.scale;
+        let offset = Vec3::new(extent, extent, extent);
+        Some(Aabb::new(self.center - offset, self.center + offset))
+    }
+}
+
+/// Blends two shapes together, filling in the creases where they meet over a distance of `k`.
+pub struct SmoothUnion {
+    a: SdfPtr,
+    b: SdfPtr,
+    k: f64,
+}
+
+impl SmoothUnion {
+    pub fn new(a: SdfPtr, b: SdfPtr, k: f64) -> SmoothUnion {
+        SmoothUnion { a, b, k }
+    }
+}
+
+impl SignedDistance for SmoothUnion {
+    fn distance(&self, p: &Point) -> f64 {
+        let (d1, d2) = (self.a.distance(p), self.b.distance(p));
+        let h = clamp(0.5 + 0.5 * (d2 - d1) / self.k, 0.0, 1.0);
+        d2 + (d1 - d2) * h - self.k * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = Aabb::surrounding_box(&self.a.bounding_box()?, &self.b.bounding_box()?);
+        Some(pad(bbox, self.k))
+    }
+}
+
+/// Carves `b` out of `a`, rounding off the edges of the cut over a distance of `k`.
+pub struct SmoothSubtraction {
+    a: SdfPtr,
+    b: SdfPtr,
+    k: f64,
+}
+
+impl SmoothSubtraction {
+    pub fn new(a: SdfPtr, b: SdfPtr, k: f64) -> SmoothSubtraction {
+        SmoothSubtraction { a, b, k }
+    }
+}
+
+impl SignedDistance for SmoothSubtraction {
+    fn distance(&self, p: &Point) -> f64 {
+        let (d1, d2) = (self.a.distance(p), -self.b.distance(p));
+        let h = clamp(0.5 + 0.5 * (d2 - d1) / self.k, 0.0, 1.0);
+        d1 + (d2 - d1) * h + self.k * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.a.bounding_box()
+    }
+}
+
+/// Repeats a shape every `period` along each axis, `count` times in each direction away from the
+/// original. Axes with a period of zero are not repeated. The shape should fit within one period
+/// for the distance to stay correct.
+pub struct Repeat {
+    shape: SdfPtr,
+    period: Vec3,
+    count: [u32; 3],
+}
+
+impl Repeat {
+    pub fn new(shape: SdfPtr, period: Vec3, count: [u32; 3]) -> Repeat {
+        Repeat {
+            shape,
+            period,
+            count,
+        }
+    }
+}
+
+impl SignedDistance for Repeat {
+    fn distance(&self, p: &Point) -> f64 {
+        let mut q = *p;
+        for axis in 0..3 {
+            let period = self.period[axis];
+            if period != 0.0 {
+                let count = self.count[axis as usize] as f64;
+                let cell = clamp((p[axis] / period).round(), -count, count);
+                q[axis] = p[axis] - period * cell;
+            }
+        }
+        self.shape.distance(&q)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = self.shape.bounding_box()?;
+        let mut extent = Vec3::default();
+        for axis in 0..3 {
+            extent[axis] = self.period[axis].abs() * self.count[axis as usize] as f64;
+        }
+        Some(Aabb::new(bbox.min() - extent, bbox.max() + extent))
+    }
+}
+
+/// Renders a signed distance function by sphere tracing: marching along the ray by the distance
+/// to the nearest surface until it is close enough to count as a hit.
+pub struct Sdf {
+    shape: SdfPtr,
+    mat_ptr: MaterialPtr,
+}
+
+impl Sdf {
+    const MAX_STEPS: u32 = 512;
+    const MAX_DISTANCE: f64 = 1e4;
+    const EPSILON: f64 = 1e-5;
+
+    pub fn new(shape: SdfPtr, m: MaterialPtr) -> Sdf {
+        Sdf { shape, mat_ptr: m }
+    }
+
+    // Estimates the gradient with the tetrahedron technique, which needs only four evaluations
+    fn normal(&self, p: &Point) -> Vec3 {
+        const H: f64 = 1e-5;
+        let k = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        k.iter()
+            .map(|k| k * self.shape.distance(&(p + H * k)))
+            .fold(Vec3::default(), |acc, v| acc + v)
+            .unit_vector()
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t_start, t_end) = match self.shape.bounding_box() {
+            Some(bbox) => bbox.hit_interval(r, t_min, t_max)?,
+            None => (t_min, t_max),
+        };
+
+        // The distance is in world units, so convert it for rays with unnormalized directions
+        let dir_len = r.direction().length();
+        let t_end = t_end.min(Sdf::MAX_DISTANCE / dir_len);
+
+        // March towards the surface from whichever side the ray starts on
+        let side = self.shape.distance(&r.at(t_start)).signum();
+        let mut t = t_start;
+        for _ in 0..Sdf::MAX_STEPS {
+            let p = r.at(t);
+            let d = side * self.shape.distance(&p);
+            if d < Sdf::EPSILON * (1.0 + t * dir_len) {
+                let outward_normal = self.normal(&p);
+                let (front_face, normal) = HitRecord::get_face_normal(r, outward_normal);
+                let theta = (-outward_normal.y()).acos();
+                let phi = (-outward_normal.z()).atan2(outward_normal.x()) + PI;
+                return Some(HitRecord {
+                    p,
+                    normal,
+                    mat_ptr: Arc::clone(&self.mat_ptr),
+                    t,
+                    u: phi / (2.0 * PI),
+                    v: theta / PI,
+                    dpdu: Vec3::new(outward_normal.z(), 0.0, -outward_normal.x()),
+                    front_face,
+                });
+            }
+
+            t += d / dir_len;
+            if t > t_end {
+                break;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.shape.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random_double, test_material};
+
+    fn shapes() -> Vec<SdfPtr> {
+        let rounded_box: SdfPtr = Arc::new(SdfRoundedBox::new(
+            Point::new(0.0, 0.5, 0.0),
+            Vec3::new(1.0, 0.5, 0.8),
+            0.2,
+        ));
+        let capsule: SdfPtr = Arc::new(SdfCapsule::new(
+            Point::new(-1.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+            0.3,
+        ));
+        let torus: SdfPtr = Arc::new(SdfTorus::new(Point::new(0.0, 0.0, 0.0), 1.0, 0.25));
+        vec![
+            Arc::clone(&rounded_box),
+            Arc::clone(&capsule),
+            Arc::clone(&torus),
+            Arc::new(Mandelbulb::new(Point::new(0.0, 0.0, 0.0), 1.0, 8.0, 8)),
+            Arc::new(SmoothUnion::new(
+                Arc::clone(&rounded_box),
+                Arc::clone(&capsule),
+                0.3,
+            )),
+            Arc::new(SmoothSubtraction::new(rounded_box, capsule, 0.3)),
+            Arc::new(Repeat::new(torus, Vec3::new(3.0, 0.0, 3.0), [2, 0, 1])),
+        ]
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let rounded_box =
+            SdfRoundedBox::new(Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0), 0.5);
+        assert!((rounded_box.distance(&Point::new(0.0, 0.0, 0.0)) + 1.0).abs() < 1e-12);
+        assert!((rounded_box.distance(&Point::new(4.0, 0.0, 0.0)) - 3.0).abs() < 1e-12);
+
+        let capsule = SdfCapsule::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 0.5);
+        assert!((capsule.distance(&Point::new(1.0, 1.0, 0.0)) - 0.5).abs() < 1e-12);
+        assert!((capsule.distance(&Point::new(0.0, 4.0, 0.0)) - 1.5).abs() < 1e-12);
+
+        let torus = SdfTorus::new(Point::new(0.0, 0.0, 0.0), 2.0, 0.5);
+        assert!((torus.distance(&Point::new(0.0, 0.0, 2.0)) + 0.5).abs() < 1e-12);
+        assert!((torus.distance(&Point::new(0.0, 0.0, 0.0)) - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn combinators() {
+        let a: SdfPtr = Arc::new(SdfCapsule::new(
+            Point::new(-2.0, 0.0, 0.0),
+            Point::new(-1.0, 0.0, 0.0),
+            0.5,
+        ));
+        let b: SdfPtr = Arc::new(SdfCapsule::new(
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(2.0, 0.0, 0.0),
+            0.5,
+        ));
+
+        // Far from where the shapes meet, blending changes nothing
+        let union = SmoothUnion::new(Arc::clone(&a), Arc::clone(&b), 0.1);
+        let p = Point::new(-1.5, 3.0, 0.0);
+        assert!((union.distance(&p) - a.distance(&p)).abs() < 1e-12);
+        let subtraction = SmoothSubtraction::new(Arc::clone(&a), Arc::clone(&b), 0.1);
+        assert!((subtraction.distance(&p) - a.distance(&p)).abs() < 1e-12);
+
+        // Every copy is the same shape, and nothing repeats past the last one
+        let repeat = Repeat::new(Arc::clone(&a), Vec3::new(0.0, 4.0, 0.0), [0, 2, 0]);
+        let p = Point::new(-1.5, 0.3, 0.2);
+        for cell in [-2.0, -1.0, 1.0, 2.0] {
+            let copy = p + Vec3::new(0.0, 4.0 * cell, 0.0);
+            assert!((repeat.distance(&copy) - a.distance(&p)).abs() < 1e-12);
+        }
+        assert!(repeat.distance(&Point::new(-1.5, 12.0, 0.0)) > 3.0);
+    }
+
+    #[test]
+    fn bounding_boxes_contain_the_inside() {
+        for shape in shapes() {
+            let bbox = shape.bounding_box().unwrap();
+            for _ in 0..20000 {
+                let p = Point::new(
+                    random_double(-8.0, 8.0),
+                    random_double(-3.0, 3.0),
+                    random_double(-8.0, 8.0),
+                );
+                if shape.distance(&p) <= 0.0 {
+                    for axis in 0..3u8 {
+                        assert!(bbox.min()[axis] <= p[axis] && p[axis] <= bbox.max()[axis]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_tracing_finds_the_surface() {
+        let sdf = Sdf::new(
+            Arc::new(SdfTorus::new(Point::new(0.0, 0.0, 0.0), 2.0, 0.5)),
+            test_material(),
+        );
+
+        // From above onto the ring, and from inside the tube out through its top
+        let r = Ray::new(Point::new(2.0, 5.0, 0.0), Vec3::new(0.0, -2.0, 0.0), 0.0);
+        let rec = sdf.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.p - Point::new(2.0, 0.5, 0.0)).length() < 1e-4);
+        assert!((rec.t - 2.25).abs() < 1e-4);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-3);
+
+        let r = Ray::new(Point::new(2.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let rec = sdf.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.p - Point::new(2.0, 0.5, 0.0)).length() < 1e-4);
+        assert!(!rec.front_face);
+
+        // Through the hole in the middle
+        let r = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(sdf.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,321 @@
+use std::ops;
+use std::sync::OnceLock;
+
+use crate::{random_double, Color};
+
+/// The range of visible wavelengths in nanometres.
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+
+/// The number of wavelengths carried along each path.
+pub const N_SPECTRUM_SAMPLES: usize = 4;
+
+/// The wavelengths carried along a path. The first is the hero wavelength, and the others are
+/// spread evenly across the visible range from it, so a single random number covers the spectrum.
+#[derive(Debug, Copy, Clone)]
+pub struct SampledWavelengths {
+    lambda: [f64; N_SPECTRUM_SAMPLES],
+    pdf: [f64; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample_uniform(u: f64) -> SampledWavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [0.0; N_SPECTRUM_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            let offset = u + i as f64 / N_SPECTRUM_SAMPLES as f64;
+            *l = LAMBDA_MIN + range * (offset - offset.floor());
+        }
+        SampledWavelengths {
+            lambda,
+            pdf: [1.0 / range; N_SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub fn random() -> SampledWavelengths {
+        SampledWavelengths::sample_uniform(random_double(0.0, 1.0))
+    }
+
+    pub fn lambda(&self, i: usize) -> f64 {
+        self.lambda[i]
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    /// Drops all but the hero wavelength, for interactions such as dispersive refraction that
+    /// send each wavelength in a different direction.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in self.pdf[1..].iter_mut() {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= N_SPECTRUM_SAMPLES as f64;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&pdf| pdf == 0.0)
+    }
+}
+
+/// Values of a spectral quantity at each of a path's `SampledWavelengths`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct SampledSpectrum {
+    values: [f64; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn new(values: [f64; N_SPECTRUM_SAMPLES]) -> SampledSpectrum {
+        SampledSpectrum { values }
+    }
+
+    pub fn constant(value: f64) -> SampledSpectrum {
+        SampledSpectrum::new([value; N_SPECTRUM_SAMPLES])
+    }
+
+    /// Evaluates `f` at each of the wavelengths.
+    pub fn from_fn(lambdas: &SampledWavelengths, f: impl Fn(f64) -> f64) -> SampledSpectrum {
+        SampledSpectrum::new(lambdas.lambda.map(f))
+    }
+
+    /// Upsamples an RGB color to a smooth spectrum built from three overlapping bands that sum
+    /// to one, so that reflectances stay in [0,1] and white stays flat.
+    pub fn from_rgb(color: &Color, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum::from_fn(lambdas, |lambda| {
+            let red = smoothstep(580.0, 600.0, lambda);
+            let blue = 1.0 - smoothstep(450.0, 530.0, lambda);
+            let green = 1.0 - red - blue;
+            color.x() * red + color.y() * green + color.z() * blue
+        })
+    }
+
+    pub fn map(&self, f: impl Fn(f64) -> f64) -> SampledSpectrum {
+        SampledSpectrum::new(self.values.map(f))
+    }
+
+    pub fn average(&self) -> f64 {
+        self.values.iter().sum::<f64>() / N_SPECTRUM_SAMPLES as f64
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.values.iter().all(|&v| v == 0.0)
+    }
+
+    /// Projects the spectrum onto the CIE XYZ matching functions and converts the result to
+    /// linear sRGB, balanced so that a flat spectrum of one is white.
+    pub fn to_rgb(&self, lambdas: &SampledWavelengths) -> Color {
+        let mut xyz = Color::new(0.0, 0.0, 0.0);
+        for i in 0..N_SPECTRUM_SAMPLES {
+            if lambdas.pdf[i] != 0.0 {
+                xyz += self.values[i] / lambdas.pdf[i] * cie_xyz(lambdas.lambda[i]);
+            }
+        }
+        xyz /= N_SPECTRUM_SAMPLES as f64 * CIE_Y_INTEGRAL;
+        let rgb = xyz_to_linear_srgb(&xyz);
+        let white = white_balance();
+        Color::new(
+            rgb.x() / white.x(),
+            rgb.y() / white.y(),
+            rgb.z() / white.z(),
+        )
+    }
+}
+
+impl ops::Index<usize> for SampledSpectrum {
+    type Output = f64;
+    fn index(&self, index: usize) -> &f64 {
+        &self.values[index]
+    }
+}
+
+impl ops::IndexMut<usize> for SampledSpectrum {
+    fn index_mut(&mut self, index: usize) -> &mut f64 {
+        &mut self.values[index]
+    }
+}
+
+impl_op_ex!(+ |lhs: &SampledSpectrum, rhs: &SampledSpectrum| -> SampledSpectrum {
+    let mut out = *lhs;
+    out += rhs;
+    out
+});
+impl_op_ex!(
+    *|lhs: &SampledSpectrum, rhs: &SampledSpectrum| -> SampledSpectrum {
+        let mut out = *lhs;
+        out *= rhs;
+        out
+    }
+);
+impl_op_ex!(*|lhs: &SampledSpectrum, rhs: f64| -> SampledSpectrum { lhs.map(|v| v * rhs) });
+impl_op_ex!(*|lhs: f64, rhs: &SampledSpectrum| -> SampledSpectrum { rhs.map(|v| lhs * v) });
+impl_op_ex!(/ |lhs: &SampledSpectrum, rhs: f64| -> SampledSpectrum { lhs.map(|v| v / rhs) });
+impl_op_ex!(+= |s: &mut SampledSpectrum, rhs: &SampledSpectrum| {
+    for i in 0..N_SPECTRUM_SAMPLES {
+        s.values[i] += rhs.values[i];
+    }
+});
+impl_op_ex!(*= |s: &mut SampledSpectrum, rhs: &SampledSpectrum| {
+    for i in 0..N_SPECTRUM_SAMPLES {
+        s.values[i] *= rhs.values[i];
+    }
+});
+
+// Wavelengths in nanometres standing in for the red, green and blue channels
+pub(crate) const CHANNEL_WAVELENGTHS: [f64; 3] = [630.0, 532.0, 465.0];
+
+/// Interpolates a quantity given per color channel, such as an index of refraction, linearly
+/// between the channels' representative wavelengths.
+pub(crate) fn interpolate_channels(color: &Color, lambda: f64) -> f64 {
+    let [red, green, blue] = CHANNEL_WAVELENGTHS;
+    if lambda >= red {
+        color.x()
+    } else if lambda >= green {
+        let t = (lambda - green) / (red - green);
+        color.y() + t * (color.x() - color.y())
+    } else if lambda >= blue {
+        let t = (lambda - blue) / (green - blue);
+        color.z() + t * (color.y() - color.z())
+    } else {
+        color.z()
+    }
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// The integral of the CIE Y matching function over the visible range
+const CIE_Y_INTEGRAL: f64 = 106.856895;
+
+/// The CIE 1931 colour matching functions at a wavelength in nanometres, using the multi-lobe
+/// fit of Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: f64) -> Color {
+    let g = |mu: f64, sigma1: f64, sigma2: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+    Color::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+fn xyz_to_linear_srgb(xyz: &Color) -> Color {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Color::new(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+// Integrates a spectrum over the CIE matching functions at 1nm steps and converts it to linear
+// sRGB, without balancing
+fn integrate_to_srgb(f: impl Fn(f64) -> f64) -> Color {
+    let mut xyz = Color::new(0.0, 0.0, 0.0);
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        xyz += f(lambda) * cie_xyz(lambda);
+        lambda += 1.0;
+    }
+    xyz_to_linear_srgb(&(xyz / CIE_Y_INTEGRAL))
+}
+
+// The linear sRGB color of a flat spectrum of one
+fn white_balance() -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    *WHITE.get_or_init(|| integrate_to_srgb(|_| 1.0))
+}
+
+/// Emission of a black body at `temperature` kelvin and a wavelength in nanometres, normalized so
+/// that its peak is one.
+pub fn blackbody(lambda: f64, temperature: f64) -> f64 {
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+    // Planck's law, with the wavelength in metres
+    let planck = |lambda: f64| {
+        const C: f64 = 299792458.0;
+        const H: f64 = 6.62606957e-34;
+        const KB: f64 = 1.3806488e-23;
+        let l = lambda * 1e-9;
+        2.0 * H * C * C / (l.powi(5) * ((H * C / (l * KB * temperature)).exp() - 1.0))
+    };
+    // Wien's displacement law gives the peak
+    let lambda_max = 2.8977721e-3 / temperature * 1e9;
+    planck(lambda) / planck(lambda_max)
+}
+
+/// The linear sRGB color of `blackbody`, interpolated from a table up to 12000K.
+pub fn blackbody_rgb(temperature: f64) -> Color {
+    const STEP: f64 = 100.0;
+    const ENTRIES: usize = 121;
+    static TABLE: OnceLock<Vec<Color>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let white = white_balance();
+        (0..ENTRIES)
+            .map(|i| {
+                let rgb = integrate_to_srgb(|lambda| blackbody(lambda, i as f64 * STEP));
+                // Deep reds fall outside the sRGB gamut
+                Color::new(
+                    (rgb.x() / white.x()).max(0.0),
+                    (rgb.y() / white.y()).max(0.0),
+                    (rgb.z() / white.z()).max(0.0),
+                )
+            })
+            .collect()
+    });
+
+    let x = (temperature / STEP).clamp(0.0, (ENTRIES - 1) as f64);
+    let i = (x as usize).min(ENTRIES - 2);
+    let t = x - i as f64;
+    (1.0 - t) * table[i] + t * table[i + 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Averages the RGB estimate of a spectrum over evenly spread wavelength samples
+    fn round_trip(color: &Color, terminate_secondary: bool) -> Color {
+        const N: usize = 1000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for k in 0..N {
+            let mut lambdas = SampledWavelengths::sample_uniform((k as f64 + 0.5) / N as f64);
+            let spectrum = SampledSpectrum::from_rgb(color, &lambdas);
+            if terminate_secondary {
+                lambdas.terminate_secondary();
+            }
+            sum += spectrum.to_rgb(&lambdas);
+        }
+        sum / N as f64
+    }
+
+    fn assert_close(a: Color, b: Color) {
+        assert!((a - b).length() < 1e-3, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn white_is_flat() {
+        let lambdas = SampledWavelengths::sample_uniform(0.37);
+        let white = SampledSpectrum::from_rgb(&Color::new(1.0, 1.0, 1.0), &lambdas);
+        for i in 0..N_SPECTRUM_SAMPLES {
+            assert!((white[i] - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn flat_white_round_trips() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        assert_close(round_trip(&white, false), white);
+        assert_close(round_trip(&white, true), white);
+
+        let grey = Color::new(0.25, 0.25, 0.25);
+        assert_close(round_trip(&grey, false), grey);
+    }
+}
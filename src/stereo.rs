@@ -0,0 +1,208 @@
+use crate::camera::spherical_direction;
+use crate::{
+    degrees_to_radians, random_double, Camera, CameraBasis, Degrees, PerspectiveCamera, Point, Ray,
+    Vec3, PI,
+};
+
+/// A pair of perspective views rendered side by side, left eye on the left, for stereo displays
+/// and headsets. The eyes sit `ipd` apart along the basis' `u` and look in parallel, with their
+/// views shifted so that they line up at `convergence` distance. Objects there appear at the
+/// depth of the screen, nearer ones in front of it. `aspect_ratio` is that of each eye's half of
+/// the image.
+pub struct StereoCamera {
+    left: PerspectiveCamera,
+    right: PerspectiveCamera,
+}
+
+impl StereoCamera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lookfrom: Point,
+        lookat: Point,
+        vup: Vec3,
+        vfov: Degrees,
+        aspect_ratio: f64,
+        ipd: f64,
+        convergence: f64,
+        time0: f64,
+        time1: f64,
+    ) -> StereoCamera {
+        let basis = CameraBasis::new(lookfrom, lookat, vup);
+        let viewport_width =
+            convergence * 2.0 * (degrees_to_radians(vfov).0 / 2.0).tan() * aspect_ratio;
+        let shift = ipd / 2.0 / viewport_width;
+
+        let eye = |side: f64| {
+            let offset = side * ipd / 2.0 * basis.u();
+            PerspectiveCamera::new(
+                lookfrom + offset,
+                lookat + offset,
+                vup,
+                vfov,
+                aspect_ratio,
+                0.0,
+                convergence,
+                time0,
+                time1,
+            )
+            .with_shift(-side * shift, 0.0)
+        };
+
+        StereoCamera {
+            left: eye(-1.0),
+            right: eye(1.0),
+        }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        if s < 0.5 {
+            self.left.get_ray(2.0 * s, t)
+        } else {
+            self.right.get_ray(2.0 * s - 1.0, t)
+        }
+    }
+}
+
+/// Omnidirectional stereo: two equirectangular panoramas, left eye on top and right eye below,
+/// for viewing in VR headsets. Each column's rays start from eyes `ipd` apart on a circle, as if
+/// the viewer turned their head to look in that direction. The separation fades out towards
+/// the poles, where it would otherwise swap the eyes over.
+pub struct OdsCamera {
+    basis: CameraBasis,
+    ipd: f64,
+    convergence: f64,
+    time0: f64,
+    time1: f64,
+}
+
+impl OdsCamera {
+    pub fn new(
+        lookfrom: Point,
+        lookat: Point,
+        vup: Vec3,
+        ipd: f64,
+        time0: f64,
+        time1: f64,
+    ) -> OdsCamera {
+        OdsCamera {
+            basis: CameraBasis::new(lookfrom, lookat, vup),
+            ipd,
+            convergence: f64::INFINITY,
+            time0,
+            time1,
+        }
+    }
+
+    /// Turns the eyes inwards to meet at `convergence` distance, instead of looking in parallel.
+    pub fn with_convergence(mut self, convergence: f64) -> OdsCamera {
+        self.convergence = convergence;
+        self
+    }
+}
+
+impl Camera for OdsCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (side, t) = if t >= 0.5 {
+            (-1.0, 2.0 * t - 1.0)
+        } else {
+            (1.0, 2.0 * t)
+        };
+
+        let longitude = 2.0 * PI * (s - 0.5);
+        let latitude = PI * (t - 0.5);
+        let direction = spherical_direction(longitude, latitude);
+
+        let radius = side * self.ipd / 2.0 * latitude.cos();
+        let eye = radius * Vec3::new(longitude.cos(), 0.0, -longitude.sin());
+        let direction = if self.convergence.is_finite() {
+            self.convergence * direction - eye
+        } else {
+            direction
+        };
+
+        Some(Ray::new(
+            self.basis.origin() + self.basis.local(&eye),
+            self.basis.local(&direction),
+            random_double(self.time0, self.time1),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Looking down -z from the origin, so the right eye is towards +x
+    fn basis() -> (Point, Point, Vec3) {
+        (
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn eyes_converge_at_the_screen() {
+        let (lookfrom, lookat, vup) = basis();
+        let cam = StereoCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            Degrees(50.0),
+            1.0,
+            0.064,
+            3.0,
+            0.0,
+            1.0,
+        );
+        let left = cam.get_ray(0.25, 0.5).unwrap();
+        let right = cam.get_ray(0.75, 0.5).unwrap();
+        assert!((left.origin() - Point::new(-0.032, 0.0, 0.0)).length() < 1e-12);
+        assert!((right.origin() - Point::new(0.032, 0.0, 0.0)).length() < 1e-12);
+
+        // The same pixel of each half sees the same point at the convergence distance
+        for (s, t) in [(0.1, 0.5), (0.3, 0.8), (0.45, 0.05)] {
+            let left = cam.get_ray(s, t).unwrap();
+            let right = cam.get_ray(s + 0.5, t).unwrap();
+            let l = left.origin() + (-3.0 / left.direction().z()) * *left.direction();
+            let r = right.origin() + (-3.0 / right.direction().z()) * *right.direction();
+            assert!((l - r).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn omnidirectional_eyes_circle_the_viewer() {
+        let (lookfrom, lookat, vup) = basis();
+        let ipd = 0.064;
+        let cam = OdsCamera::new(lookfrom, lookat, vup, ipd, 0.0, 1.0);
+        for s in [0.1, 0.5, 0.7] {
+            let left = cam.get_ray(s, 0.75).unwrap();
+            let right = cam.get_ray(s, 0.25).unwrap();
+            // Both eyes look the same way from either side of the viewer
+            assert!(
+                (left.direction().unit_vector() - right.direction().unit_vector()).length() < 1e-12
+            );
+            assert!((left.origin().length() - ipd / 2.0).abs() < 1e-12);
+            assert!((left.origin() + right.origin()).length() < 1e-12);
+            assert!(Vec3::dot(left.origin(), left.direction()).abs() < 1e-12);
+            // The left eye is on the left
+            let to_right = Vec3::cross(left.direction(), &vup);
+            assert!(Vec3::dot(left.origin(), &to_right) < 0.0);
+        }
+
+        // The eyes come together at the poles
+        assert!(cam.get_ray(0.3, 1.0).unwrap().origin().length() < 1e-12);
+
+        // Converging, both eyes look at the point 2 units away in that column's direction
+        let cam = cam.with_convergence(2.0);
+        let target = CameraBasis::new(lookfrom, lookat, vup)
+            .local(&(2.0 * spherical_direction(2.0 * PI * 0.1, PI * 0.1)));
+        for t in [0.8, 0.3] {
+            let r = cam.get_ray(0.6, t).unwrap();
+            let to_target = (target - *r.origin()).unit_vector();
+            assert!((to_target - r.direction().unit_vector()).length() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::microfacet::fresnel_dielectric;
+use crate::spectrum::interpolate_channels;
+use crate::{
+    random_double, Color, HenyeyGreenstein, HitRecord, Material, PhaseFunction, Ray,
+    SampledSpectrum, SampledWavelengths, Vec3, N_SPECTRUM_SAMPLES,
+};
+
+/// Translucent materials such as skin, marble, wax and milk, where light wanders under the
+/// surface before leaving it. The object carrying it must be closed, like a sphere or a cuboid.
+///
+/// Light refracts in through a smooth boundary and takes a random walk through the interior,
+/// scattering at exponentially distributed distances until it refracts back out. Each walk step
+/// is one call to `scatter`: a ray traveling inside hits the back of the boundary, and the
+/// material decides whether it scattered somewhere along the way or reached the boundary. Walk
+/// steps report themselves through `walks_inside`, so they do not use up the path's bounces.
+pub struct Subsurface {
+    // Single scattering albedo and extinction coefficient, per color channel
+    albedo: Color,
+    sigma_t: Color,
+    ir: f64,
+    phase: HenyeyGreenstein,
+}
+
+impl Subsurface {
+    /// `albedo` is the overall color of the material and `mean_free_path` is how far light
+    /// travels inside before scattering, for each color channel. Skin, for example, lets red
+    /// light travel much further than blue.
+    pub fn new(albedo: Color, mean_free_path: Color, ir: f64) -> Subsurface {
+        let mut single_scattering = albedo;
+        let mut sigma_t = mean_free_path;
+        for i in 0..3 {
+            single_scattering[i] = Subsurface::single_scattering_albedo(albedo[i]);
+            sigma_t[i] = 1.0 / mean_free_path[i].max(1e-6);
+        }
+        Subsurface {
+            albedo: single_scattering,
+            sigma_t,
+            ir,
+            phase: HenyeyGreenstein::new(0.0),
+        }
+    }
+
+    /// Sets the asymmetry of scattering inside, from -1 (backwards) to 1 (forwards).
+    pub fn with_anisotropy(mut self, g: f64) -> Subsurface {
+        self.phase = HenyeyGreenstein::new(g);
+        self
+    }
+
+    // Maps the albedo of the whole material to the albedo of a single scattering event, so that
+    // the color after many bounces matches the one asked for (Chiang et al. 2016)
+    fn single_scattering_albedo(albedo: f64) -> f64 {
+        let a = albedo.clamp(0.0, 1.0);
+        let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+        1.0 - s * s
+    }
+
+    // Reflects off or refracts through the smooth boundary
+    fn boundary(&self, ray_in: &Ray, rec: &HitRecord) -> Ray {
+        let unit_direction = ray_in.direction().unit_vector();
+        let cos_theta = Vec3::dot(&-unit_direction, &rec.normal).min(1.0);
+        let eta = if rec.front_face {
+            self.ir
+        } else {
+            1.0 / self.ir
+        };
+
+        let direction = if random_double(0.0, 1.0) < fresnel_dielectric(cos_theta, eta) {
+            Vec3::reflect(&unit_direction, &rec.normal)
+        } else {
+            Vec3::refract(&unit_direction, &rec.normal, 1.0 / eta)
+        };
+        Ray::new(rec.p, direction, ray_in.time())
+    }
+
+    // One step of the walk for a ray inside, given the single scattering albedo and extinction
+    // coefficient of each color channel or wavelength. A distance to the next scattering event
+    // is sampled from a randomly chosen channel. Each channel's weight divides by the average
+    // density of the channels, which keeps the estimate unbiased whichever one was picked.
+    fn walk<const N: usize>(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        albedo: [f64; N],
+        sigma_t: [f64; N],
+    ) -> Option<([f64; N], Ray)> {
+        let ray_length = ray_in.direction().length();
+        let distance = rec.t * ray_length;
+        let channel = (random_double(0.0, N as f64) as usize).min(N - 1);
+        let flight = -(1.0 - random_double(0.0, 1.0)).ln() / sigma_t[channel];
+
+        if flight < distance {
+            let transmittance = sigma_t.map(|sigma_t| (-sigma_t * flight).exp());
+            let mut weight = albedo;
+            let mut pdf = 0.0;
+            for i in 0..N {
+                weight[i] *= sigma_t[i] * transmittance[i];
+                pdf += sigma_t[i] * transmittance[i] / N as f64;
+            }
+
+            let unit_direction = ray_in.direction().unit_vector();
+            let p = ray_in.origin() + flight * unit_direction;
+            let scattered = Ray::new(p, self.phase.sample(&unit_direction), ray_in.time());
+            return Some((weight.map(|w| w / pdf), scattered));
+        }
+
+        let transmittance = sigma_t.map(|sigma_t| (-sigma_t * distance).exp());
+        let pdf = transmittance.iter().sum::<f64>() / N as f64;
+        if pdf == 0.0 {
+            return None;
+        }
+        Some((transmittance.map(|t| t / pdf), self.boundary(ray_in, rec)))
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        if rec.front_face {
+            return Some((Color::new(1.0, 1.0, 1.0), self.boundary(ray_in, rec)));
+        }
+
+        let albedo = [self.albedo.x(), self.albedo.y(), self.albedo.z()];
+        let sigma_t = [self.sigma_t.x(), self.sigma_t.y(), self.sigma_t.z()];
+        let ([r, g, b], scattered) = self.walk(ray_in, rec, albedo, sigma_t)?;
+        Some((Color::new(r, g, b), scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        if rec.front_face {
+            return Some((SampledSpectrum::constant(1.0), self.boundary(ray_in, rec)));
+        }
+
+        let mut albedo = [0.0; N_SPECTRUM_SAMPLES];
+        let mut sigma_t = [0.0; N_SPECTRUM_SAMPLES];
+        for i in 0..N_SPECTRUM_SAMPLES {
+            albedo[i] = interpolate_channels(&self.albedo, lambdas.lambda(i));
+            sigma_t[i] = interpolate_channels(&self.sigma_t, lambdas.lambda(i));
+        }
+        let (weight, scattered) = self.walk(ray_in, rec, albedo, sigma_t)?;
+        Some((SampledSpectrum::new(weight), scattered))
+    }
+
+    // Rays inside the material are always partway through a walk
+    fn walks_inside(&self, _ray_in: &Ray, rec: &HitRecord) -> bool {
+        !rec.front_face
+    }
+}
@@ -0,0 +1,111 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{clamp, Color, Image, Point};
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+}
+
+pub type TexturePtr = Arc<dyn Texture + Send + Sync>;
+
+pub struct SolidColor {
+    color_value: Color,
+}
+
+impl SolidColor {
+    pub fn new(color_value: Color) -> SolidColor {
+        SolidColor { color_value }
+    }
+
+    /// A constant for textures that drive a single parameter, such as roughness.
+    pub fn from_value(value: f64) -> SolidColor {
+        SolidColor::new(Color::new(value, value, value))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _: f64, _: f64, _: &Point) -> Color {
+        self.color_value
+    }
+}
+
+/// A 3D checkerboard alternating between two textures every `1 / scale` units.
+pub struct CheckerTexture {
+    odd: TexturePtr,
+    even: TexturePtr,
+    scale: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(even: TexturePtr, odd: TexturePtr, scale: f64) -> CheckerTexture {
+        CheckerTexture { odd, even, scale }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let cell = (self.scale * p.x()).floor()
+            + (self.scale * p.y()).floor()
+            + (self.scale * p.z()).floor();
+        if cell as i64 % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Looks up colors from an image by texture coordinates, with `v` running from the bottom of the
+/// image to the top. Grayscale images are treated as gray colors.
+pub struct ImageTexture {
+    image: Image,
+    alpha: bool,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> ImageTexture {
+        ImageTexture {
+            image,
+            alpha: false,
+        }
+    }
+
+    /// Looks up the alpha channel of gray-alpha and RGBA images as a gray color. Images without
+    /// alpha are fully opaque, so they give white.
+    pub fn alpha(image: Image) -> ImageTexture {
+        ImageTexture { image, alpha: true }
+    }
+
+    pub fn load_png(path: impl AsRef<Path>) -> io::Result<ImageTexture> {
+        Ok(ImageTexture::new(Image::load_png(path)?))
+    }
+
+    pub fn load_png_alpha(path: impl AsRef<Path>) -> io::Result<ImageTexture> {
+        Ok(ImageTexture::alpha(Image::load_png(path)?))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _: &Point) -> Color {
+        let u = clamp(u, 0.0, 1.0);
+        let v = 1.0 - clamp(v, 0.0, 1.0);
+
+        let i = ((u * self.image.width() as f64) as usize).min(self.image.width() - 1);
+        let j = ((v * self.image.height() as f64) as usize).min(self.image.height() - 1);
+        let pixel = self.image.pixel(i, j);
+        if self.alpha {
+            let a = match pixel.len() {
+                2 | 4 => pixel[pixel.len() - 1],
+                _ => 1.0,
+            };
+            return Color::new(a, a, a);
+        }
+
+        match pixel.len() {
+            1 | 2 => Color::new(pixel[0], pixel[0], pixel[0]),
+            _ => Color::new(pixel[0], pixel[1], pixel[2]),
+        }
+    }
+}
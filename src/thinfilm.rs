@@ -0,0 +1,151 @@
+use crate::spectrum::CHANNEL_WAVELENGTHS;
+use crate::{
+    random_double, Color, HitRecord, Material, Ray, SampledSpectrum, SampledWavelengths,
+    TexturePtr, Vec3, PI,
+};
+
+/// Reflectance of a thin film of index `n2` and thickness `thickness` (in nanometres) between
+/// media of index `n1` and `n3`, for light of the given wavelength arriving from the `n1` side at
+/// `cos_theta_i` to the normal. Light reflected from the two sides of the film interferes, which
+/// gives the colors of soap bubbles and oil slicks.
+pub(crate) fn thin_film_reflectance(
+    cos_theta_i: f64,
+    n1: f64,
+    n2: f64,
+    n3: f64,
+    thickness: f64,
+    lambda: f64,
+) -> f64 {
+    let cos1 = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_1 = 1.0 - cos1 * cos1;
+    let sin2_2 = sin2_1 * (n1 / n2).powi(2);
+    let sin2_3 = sin2_1 * (n1 / n3).powi(2);
+    if sin2_2 >= 1.0 || sin2_3 >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos2 = (1.0 - sin2_2).sqrt();
+    let cos3 = (1.0 - sin2_3).sqrt();
+
+    let phase = 4.0 * PI * n2 * thickness * cos2 / lambda;
+    let airy = |r12: f64, r23: f64| {
+        let cross = 2.0 * r12 * r23 * phase.cos();
+        (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+    };
+
+    let r12_s = (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2);
+    let r23_s = (n2 * cos2 - n3 * cos3) / (n2 * cos2 + n3 * cos3);
+    let r12_p = (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2);
+    let r23_p = (n3 * cos2 - n2 * cos3) / (n3 * cos2 + n2 * cos3);
+    0.5 * (airy(r12_s, r23_s) + airy(r12_p, r23_p))
+}
+
+/// A free-standing film with air on both sides, such as a soap bubble. Light is either reflected
+/// or passes straight through.
+#[derive(Clone)]
+pub struct ThinFilm {
+    thickness: f64,
+    thickness_texture: Option<TexturePtr>,
+    ior: f64,
+}
+
+impl ThinFilm {
+    /// A film `thickness` nanometres thick with index of refraction `ior`.
+    pub fn new(thickness: f64, ior: f64) -> ThinFilm {
+        ThinFilm {
+            thickness,
+            thickness_texture: None,
+            ior,
+        }
+    }
+
+    /// Scales the thickness across the surface by the first channel of `texture`, for the swirls
+    /// of a soap bubble.
+    pub fn with_thickness_texture(mut self, texture: TexturePtr) -> ThinFilm {
+        self.thickness_texture = Some(texture);
+        self
+    }
+
+    pub(crate) fn thickness_at(&self, rec: &HitRecord) -> f64 {
+        match &self.thickness_texture {
+            Some(texture) => self.thickness * texture.value(rec.u, rec.v, &rec.p).x().max(0.0),
+            None => self.thickness,
+        }
+    }
+
+    /// Reflectance of the film lying on a substrate of index `substrate`.
+    pub(crate) fn reflectance(
+        &self,
+        thickness: f64,
+        cos_theta: f64,
+        substrate: f64,
+        lambda: f64,
+    ) -> f64 {
+        thin_film_reflectance(cos_theta, 1.0, self.ior, substrate, thickness, lambda)
+    }
+
+    pub(crate) fn reflectance_rgb(&self, thickness: f64, cos_theta: f64, substrate: f64) -> Color {
+        let [r, g, b] = CHANNEL_WAVELENGTHS
+            .map(|lambda| self.reflectance(thickness, cos_theta, substrate, lambda));
+        Color::new(r, g, b)
+    }
+
+    // Reflects off the film or passes straight through it
+    fn sample(ray_in: &Ray, rec: &HitRecord, reflect_probability: f64) -> (Ray, bool) {
+        if random_double(0.0, 1.0) < reflect_probability {
+            let reflected = Vec3::reflect(&ray_in.direction().unit_vector(), &rec.normal);
+            (Ray::new(rec.p, reflected, ray_in.time()), true)
+        } else {
+            (Ray::new(rec.p, *ray_in.direction(), ray_in.time()), false)
+        }
+    }
+
+    fn cos_theta(ray_in: &Ray, rec: &HitRecord) -> f64 {
+        Vec3::dot(&-ray_in.direction().unit_vector(), &rec.normal)
+    }
+}
+
+// Picks reflection with the average reflectance across the channels, so each channel's weight is
+// its own reflectance or transmittance divided by the chance of taking that branch
+fn choice_weight(reflectance: f64, average: f64, reflected: bool) -> f64 {
+    if reflected {
+        reflectance / average
+    } else {
+        (1.0 - reflectance) / (1.0 - average)
+    }
+}
+
+impl Material for ThinFilm {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let thickness = self.thickness_at(rec);
+        let reflectance = self.reflectance_rgb(thickness, ThinFilm::cos_theta(ray_in, rec), 1.0);
+        let average = (reflectance.x() + reflectance.y() + reflectance.z()) / 3.0;
+
+        let (scattered, reflected) = ThinFilm::sample(ray_in, rec, average);
+        let mut attenuation = reflectance;
+        for i in 0..3 {
+            attenuation[i] = choice_weight(reflectance[i], average, reflected);
+        }
+        Some((attenuation, scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        lambdas: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let thickness = self.thickness_at(rec);
+        let cos_theta = ThinFilm::cos_theta(ray_in, rec);
+        let reflectance = SampledSpectrum::from_fn(lambdas, |lambda| {
+            self.reflectance(thickness, cos_theta, 1.0, lambda)
+        });
+        let average = reflectance.average();
+
+        let (scattered, reflected) = ThinFilm::sample(ray_in, rec, average);
+        Some((
+            reflectance.map(|r| choice_weight(r, average, reflected)),
+            scattered,
+        ))
+    }
+}
@@ -0,0 +1,553 @@
+use std::ops;
+
+use crate::{
+    degrees_to_radians, Aabb, Degrees, HitRecord, Hittable, HittablePtr, Point, Ray, Vec3, PI,
+};
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut r = [[0.0; 4]; 4];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    r
+}
+
+fn transpose(m: &Matrix) -> Matrix {
+    let mut r = [[0.0; 4]; 4];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = m[j][i];
+        }
+    }
+    r
+}
+
+// Gauss-Jordan elimination with partial pivoting
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let mut a = *m;
+    let mut inv = IDENTITY;
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+
+        let scale = 1.0 / a[col][col];
+        for j in 0..4 {
+            a[col][j] *= scale;
+            inv[col][j] *= scale;
+        }
+        for row in 0..4 {
+            if row != col {
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+    }
+    Some(inv)
+}
+
+/// An affine transformation, stored together with its inverse.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    m: Matrix,
+    m_inv: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    /// Builds a transform from a row-major matrix, or `None` if the matrix is singular.
+    pub fn new(m: [[f64; 4]; 4]) -> Option<Transform> {
+        invert(&m).map(|m_inv| Transform { m, m_inv })
+    }
+
+    pub fn identity() -> Transform {
+        Transform {
+            m: IDENTITY,
+            m_inv: IDENTITY,
+        }
+    }
+
+    pub fn translate(delta: Vec3) -> Transform {
+        let (x, y, z) = (delta.x(), delta.y(), delta.z());
+        Transform {
+            m: [
+                [1.0, 0.0, 0.0, x],
+                [0.0, 1.0, 0.0, y],
+                [0.0, 0.0, 1.0, z],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+            m_inv: [
+                [1.0, 0.0, 0.0, -x],
+                [0.0, 1.0, 0.0, -y],
+                [0.0, 0.0, 1.0, -z],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Scales by a factor along each axis, or `None` if any factor is zero, which would flatten
+    /// objects and leave no inverse.
+    pub fn scale(factors: Vec3) -> Option<Transform> {
+        let (x, y, z) = (factors.x(), factors.y(), factors.z());
+        if x == 0.0 || y == 0.0 || z == 0.0 {
+            return None;
+        }
+        Some(Transform {
+            m: [
+                [x, 0.0, 0.0, 0.0],
+                [0.0, y, 0.0, 0.0],
+                [0.0, 0.0, z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+            m_inv: [
+                [1.0 / x, 0.0, 0.0, 0.0],
+                [0.0, 1.0 / y, 0.0, 0.0],
+                [0.0, 0.0, 1.0 / z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        })
+    }
+
+    /// Rotates counter-clockwise by `angle` around `axis`, looking down the axis towards the
+    /// origin.
+    pub fn rotate(angle: Degrees, axis: Vec3) -> Transform {
+        let a = axis.unit_vector();
+        let theta = degrees_to_radians(angle).0;
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let one_minus_cos = 1.0 - cos_theta;
+
+        let m = [
+            [
+                a.x() * a.x() * one_minus_cos + cos_theta,
+                a.x() * a.y() * one_minus_cos - a.z() * sin_theta,
+                a.x() * a.z() * one_minus_cos + a.y() * sin_theta,
+                0.0,
+            ],
+            [
+                a.y() * a.x() * one_minus_cos + a.z() * sin_theta,
+                a.y() * a.y() * one_minus_cos + cos_theta,
+                a.y() * a.z() * one_minus_cos - a.x() * sin_theta,
+                0.0,
+            ],
+            [
+                a.z() * a.x() * one_minus_cos - a.y() * sin_theta,
+                a.z() * a.y() * one_minus_cos + a.x() * sin_theta,
+                a.z() * a.z() * one_minus_cos + cos_theta,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+
+        // Rotation matrices are orthogonal, so the inverse is the transpose
+        Transform {
+            m,
+            m_inv: transpose(&m),
+        }
+    }
+
+    pub fn rotate_x(angle: Degrees) -> Transform {
+        Transform::rotate(angle, Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    pub fn rotate_y(angle: Degrees) -> Transform {
+        Transform::rotate(angle, Vec3::new(0.0, 1.0, 0.0))
+    }
+
+    pub fn rotate_z(angle: Degrees) -> Transform {
+        Transform::rotate(angle, Vec3::new(0.0, 0.0, 1.0))
+    }
+
+    pub fn matrix(&self) -> [[f64; 4]; 4] {
+        self.m
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+
+    /// The inverse transpose of the upper 3x3 part of the matrix, used to transform normals.
+    pub fn normal_matrix(&self) -> [[f64; 3]; 3] {
+        let mut n = [[0.0; 3]; 3];
+        for (i, row) in n.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m_inv[j][i];
+            }
+        }
+        n
+    }
+
+    pub fn point(&self, p: &Point) -> Point {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1.0 {
+            Point::new(x, y, z)
+        } else {
+            Point::new(x, y, z) / w
+        }
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    /// Transforms a surface normal. The result is not normalized.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        let m = self.normal_matrix();
+        Vec3::new(
+            m[0][0] * n.x() + m[0][1] * n.y() + m[0][2] * n.z(),
+            m[1][0] * n.x() + m[1][1] * n.y() + m[1][2] * n.z(),
+            m[2][0] * n.x() + m[2][1] * n.y() + m[2][2] * n.z(),
+        )
+    }
+
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::new(self.point(r.origin()), self.vector(r.direction()), r.time())
+    }
+
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        Aabb::from_points(&bbox.corners().map(|corner| self.point(&corner)))
+    }
+}
+
+// Composition applies the right hand side first, so `translate * rotate` rotates then translates
+impl_op_ex!(*|lhs: &Transform, rhs: &Transform| -> Transform {
+    Transform {
+        m: mul(&lhs.m, &rhs.m),
+        m_inv: mul(&rhs.m_inv, &lhs.m_inv),
+    }
+});
+
+/// Places an object in the world using an object-to-world transform.
+///
+/// The object is shared, so the same geometry can be instanced any number of times with
+/// different transforms.
+pub struct Transformed {
+    object: HittablePtr,
+    transform: Transform,
+}
+
+impl Transformed {
+    pub fn new(object: HittablePtr, transform: Transform) -> Transformed {
+        Transformed { object, transform }
+    }
+}
+
+fn hit_transformed(
+    object: &HittablePtr,
+    transform: &Transform,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    // The object space direction is not normalized, so t is the same in both spaces
+    let object_ray = transform.inverse().ray(r);
+    object
+        .hit(&object_ray, t_min, t_max)
+        .map(|rec| to_world(transform, rec))
+}
+
+fn to_world(transform: &Transform, mut rec: HitRecord) -> HitRecord {
+    rec.p = transform.point(&rec.p);
+    rec.normal = transform.normal(&rec.normal).unit_vector();
+    rec.dpdu = transform.vector(&rec.dpdu);
+    rec
+}
+
+fn hit_all_transformed(
+    object: &HittablePtr,
+    transform: &Transform,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Vec<HitRecord> {
+    let object_ray = transform.inverse().ray(r);
+    object
+        .hit_all(&object_ray, t_min, t_max)
+        .into_iter()
+        .map(|rec| to_world(transform, rec))
+        .collect()
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_transformed(&self.object, &self.transform, r, t_min, t_max)
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        hit_all_transformed(&self.object, &self.transform, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object
+            .bounding_box()
+            .map(|bbox| self.transform.bounding_box(&bbox))
+    }
+}
+
+/// The pose of an animated object at a point in time. `rotation` holds Euler angles in degrees,
+/// applied around the x, then y, then z axes.
+#[derive(Debug, Copy, Clone)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, translation: Vec3, rotation: Vec3, scale: Vec3) -> Keyframe {
+        Keyframe {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    /// The object-to-world transform of the pose, or `None` if a scale factor is zero.
+    pub fn transform(&self) -> Option<Transform> {
+        Some(
+            Transform::translate(self.translation)
+                * Transform::rotate_z(Degrees(self.rotation.z()))
+                * Transform::rotate_y(Degrees(self.rotation.y()))
+                * Transform::rotate_x(Degrees(self.rotation.x()))
+                * Transform::scale(self.scale)?,
+        )
+    }
+
+    // Interpolates each component of the pose separately, so rotations and scales stay well
+    // behaved between keyframes
+    fn lerp(a: &Keyframe, b: &Keyframe, time: f64) -> Keyframe {
+        // Keyframes sharing a time would divide by zero, so hold the first pose
+        if b.time == a.time {
+            return Keyframe { time, ..*a };
+        }
+        let s = (time - a.time) / (b.time - a.time);
+        Keyframe {
+            time,
+            translation: a.translation + s * (b.translation - a.translation),
+            rotation: a.rotation + s * (b.rotation - a.rotation),
+            scale: a.scale + s * (b.scale - a.scale),
+        }
+    }
+}
+
+/// Places an object in the world using a transform that is interpolated between keyframes
+/// according to each ray's time. Before the first and after the last keyframe the object holds
+/// still. While a pose scales it down to nothing, it cannot be hit.
+pub struct Keyframed {
+    object: HittablePtr,
+    keyframes: Vec<Keyframe>,
+}
+
+impl Keyframed {
+    pub fn new(object: HittablePtr, mut keyframes: Vec<Keyframe>) -> Keyframed {
+        if keyframes.is_empty() {
+            panic!("Keyframed objects need at least one keyframe");
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Keyframed { object, keyframes }
+    }
+
+    pub fn transform_at(&self, time: f64) -> Option<Transform> {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return first.transform();
+        }
+        if time >= last.time {
+            return last.transform();
+        }
+
+        let i = self.keyframes.partition_point(|k| k.time <= time);
+        Keyframe::lerp(&self.keyframes[i - 1], &self.keyframes[i], time).transform()
+    }
+}
+
+impl Hittable for Keyframed {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let transform = self.transform_at(r.time())?;
+        hit_transformed(&self.object, &transform, r, t_min, t_max)
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        match self.transform_at(r.time()) {
+            Some(transform) => hit_all_transformed(&self.object, &transform, r, t_min, t_max),
+            None => vec![],
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Rotations sweep the object along arcs, so sample the motion between keyframes rather
+        // than only bounding the keyframes themselves. Every pose lies within half a step of a
+        // sample, so padding each sample by how far a point can move in half a step makes the
+        // bound conservative.
+        const STEPS: usize = 16;
+        let bbox = self.object.bounding_box()?;
+        let reach = bbox
+            .corners()
+            .iter()
+            .map(|p| p.length())
+            .fold(0.0, f64::max);
+        let max_abs = |v: Vec3| v.x().abs().max(v.y().abs()).max(v.z().abs());
+
+        let mut poses = vec![(self.keyframes[0], 0.0)];
+        for pair in self.keyframes.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let half_step = 0.5 / STEPS as f64;
+            let rotation = b.rotation - a.rotation;
+            // Composing the per-axis changes turns the object by at most their sum
+            let angle = (half_step
+                * degrees_to_radians(Degrees(
+                    rotation.x().abs() + rotation.y().abs() + rotation.z().abs(),
+                ))
+                .0)
+                .min(PI);
+            let pad = half_step * (b.translation - a.translation).length()
+                + half_step * max_abs(b.scale - a.scale) * reach
+                + 2.0 * (0.5 * angle).sin() * max_abs(a.scale).max(max_abs(b.scale)) * reach;
+
+            for step in 0..=STEPS {
+                let time = a.time + (b.time - a.time) * step as f64 / STEPS as f64;
+                poses.push((Keyframe::lerp(a, b, time), pad));
+            }
+        }
+
+        // Poses that scale the object down to nothing cannot be hit, so they add nothing
+        poses
+            .iter()
+            .filter_map(|(pose, pad)| {
+                let sample = pose.transform()?.bounding_box(&bbox);
+                let pad = Vec3::new(*pad, *pad, *pad);
+                Some(Aabb::new(sample.min() - pad, sample.max() + pad))
+            })
+            .reduce(|a, b| Aabb::surrounding_box(&a, &b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{test_material, Sphere};
+
+    fn assert_identity(t: &Transform) {
+        let m = t.matrix();
+        for (i, row) in m.iter().enumerate() {
+            for (j, &x) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((x - expected).abs() < 1e-9, "{m:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn times_inverse_is_identity() {
+        let t = Transform::translate(Vec3::new(1.0, -2.0, 3.0))
+            * Transform::rotate(Degrees(37.0), Vec3::new(1.0, 2.0, -0.5))
+            * Transform::scale(Vec3::new(2.0, 0.5, -3.0)).unwrap()
+            * Transform::rotate_x(Degrees(-80.0));
+        assert_identity(&(t * t.inverse()));
+        assert_identity(&(t.inverse() * t));
+
+        let general = Transform::new([
+            [2.0, 1.0, 0.0, 4.0],
+            [0.5, 3.0, -1.0, 0.0],
+            [0.0, 1.0, 1.0, -2.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+        .unwrap();
+        assert_identity(&(general * general.inverse()));
+    }
+
+    #[test]
+    fn points_round_trip() {
+        let t = Transform::rotate_y(Degrees(30.0)) * Transform::translate(Vec3::new(0.0, 5.0, 0.0));
+        let p = Point::new(0.3, -1.2, 7.0);
+        assert!((t.inverse().point(&t.point(&p)) - p).length() < 1e-12);
+    }
+
+    #[test]
+    fn keyframed_box_bounds_the_whole_motion() {
+        let material = test_material();
+        let sphere = Sphere::new(Point::new(3.0, 0.0, 0.0), 1.0, material);
+        let keyframes = vec![
+            Keyframe::new(
+                0.0,
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                1.0,
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 270.0, 0.0),
+                Vec3::new(2.0, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                1.0,
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 90.0, 0.0),
+                Vec3::new(1.0, 1.0, 1.0),
+            ),
+        ];
+        let keyframed = Keyframed::new(Arc::new(sphere), keyframes);
+        let output_box = keyframed.bounding_box().unwrap();
+
+        let bbox = keyframed.object.bounding_box().unwrap();
+        for i in 0..=1000 {
+            let transform = keyframed.transform_at(i as f64 / 1000.0).unwrap();
+            let pose = transform.bounding_box(&bbox);
+            for k in 0..3u8 {
+                assert!(pose.min()[k] >= output_box.min()[k]);
+                assert!(pose.max()[k] <= output_box.max()[k]);
+            }
+        }
+    }
+
+    #[test]
+    fn singular_transforms_are_rejected() {
+        assert!(Transform::scale(Vec3::new(1.0, 0.0, 2.0)).is_none());
+        assert!(Transform::new([
+            [1.0, 2.0, 3.0, 0.0],
+            [2.0, 4.0, 6.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+        .is_none());
+    }
+}